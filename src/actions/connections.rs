@@ -0,0 +1,100 @@
+use std::{collections::HashMap, net::Ipv4Addr};
+
+use tracing::Instrument;
+
+use crate::configs::project_config::ProjectConfig;
+
+use super::{
+    deploy_descriptor::{Descriptor, RetryPolicy},
+    ssh_client::{self, SSHConnection, SSHErrors},
+};
+
+/// Keeps one authenticated session per target so the steps of a subcommand can share it.
+///
+/// Sessions are kept alive with keepalives, and one that was lost since it was last handed
+/// out, like after a radio blip, is replaced with a fresh connection the next time it's asked for.
+pub struct ConnectionManager<'a> {
+    descriptor: &'a Descriptor,
+    target: Ipv4Addr,
+    retry: RetryPolicy,
+    connections: HashMap<Ipv4Addr, SSHConnection>,
+}
+impl<'a> ConnectionManager<'a> {
+    pub fn new(config: &ProjectConfig, descriptor: &'a Descriptor) -> Self {
+        Self {
+            descriptor,
+            target: config.target_address(),
+            retry: RetryPolicy::default(),
+            connections: HashMap::new(),
+        }
+    }
+
+    /// The session to the robot the config selects.
+    pub async fn target(&mut self) -> Result<&mut SSHConnection, SSHErrors> {
+        self.get(self.target).await
+    }
+
+    /// The session to `addr`, connecting if there is none or the last one was lost.
+    pub async fn get(&mut self, addr: Ipv4Addr) -> Result<&mut SSHConnection, SSHErrors> {
+        if self
+            .connections
+            .get(&addr)
+            .is_some_and(SSHConnection::is_closed)
+        {
+            tracing::warn!("Lost the connection to {}, reconnecting", addr);
+            self.connections.remove(&addr);
+        }
+        if !self.connections.contains_key(&addr) {
+            let connection = self
+                .connect(addr)
+                .instrument(tracing::info_span!("connect", %addr))
+                .await?;
+            self.connections.insert(addr, connection);
+        }
+        Ok(self
+            .connections
+            .get_mut(&addr)
+            .expect("a connection was just made"))
+    }
+
+    /// Drops the session to the target after a step failed with `err`,
+    /// so the step can be repeated on a fresh one. See [`retry_target!`].
+    pub fn lost_target(&mut self, err: &SSHErrors) {
+        tracing::warn!("Lost the connection to {}: {}, retrying", self.target, err);
+        self.connections.remove(&self.target);
+    }
+
+    async fn connect(&self, addr: Ipv4Addr) -> Result<SSHConnection, SSHErrors> {
+        self.retry
+            .run_if(SSHErrors::is_transient, || {
+                ssh_client::connect_ssh_client(addr, self.descriptor)
+            })
+            .await
+    }
+}
+
+/// Runs `$step` with `$connection` bound to the session to the target of a [`ConnectionManager`],
+/// and if that session was lost during it, once more from the start on a fresh session.
+///
+/// Only for steps that can be repeated as a whole, like writing a whole file.
+/// Anything else cut off by a lost session fails, the next step reconnects.
+/// Evaluates to the `Result<_, SSHErrors>` of the step or of connecting.
+macro_rules! retry_target {
+    ($connections:expr, |$connection:ident| $step:expr) => {{
+        let mut retried = false;
+        loop {
+            let $connection = match $connections.target().await {
+                Ok(connection) => connection,
+                Err(err) => break Err(err),
+            };
+            match $step {
+                Err(err) if !retried && err.is_transient() => {
+                    $connections.lost_target(&err);
+                    retried = true;
+                }
+                result => break result,
+            }
+        }
+    }};
+}
+pub(crate) use retry_target;
@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, future::Future, net::Ipv4Addr, time::Duration};
 
-use reqwest::{header::HeaderMap, Method, Url};
+use regex::Regex;
+use reqwest::{header::HeaderMap, Method, StatusCode, Url};
 use thiserror::Error;
 
 #[allow(dead_code)]
-const DEPLOY_DESCRIPTOR_SCHEMA: &str = r#"
+const DEPLOY_DESCRIPTOR_SCHEMA: &str = r##"
 {
     "schemaDescription": {
         "name": "Deploy Descriptor",
@@ -35,11 +36,36 @@ const DEPLOY_DESCRIPTOR_SCHEMA: &str = r#"
                 "type": "string",
                 "description": "The command to start the robot-code on the target"
             },
+            "rust_target": {
+                "type": "string",
+                "description": "The rust target triple to build robot-code for",
+                "default": "arm-unknown-linux-gnueabi"
+            },
             "dep_lib_path": {
                 "type": "string",
                 "description": "The path to the deploy libraries relative to the deploy directory on the target",
                 "default": "./lib"
             },
+            "run_as": {
+                "type": "string",
+                "description": "The user the robot-code runs as on the target, deployed files are owned by it",
+                "default": null
+            },
+            "robot_command": {
+                "type": "string",
+                "description": "The unix path of the file start_cmd runs the robot-code from, the deploy writes the path of the deployed binary into it",
+                "default": null
+            },
+            "log_path": {
+                "type": "string",
+                "description": "The unix path to the console log of the robot-code on the target",
+                "default": "~/FRC_UserProgram.log"
+            },
+            "datalog_globs": {
+                "type": "array",
+                "description": "Unix paths of the datalogs written on the target, globs are allowed in the file name",
+                "default": []
+            },
             "extra_files_path": {
                 "type": "string",
                 "description": "The path to the extra files relative to the deploy directory on the target",
@@ -62,6 +88,14 @@ const DEPLOY_DESCRIPTOR_SCHEMA: &str = r#"
                         "description": "Additional headers to send with the request",
                         "default": null
                     },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "description": "How long to wait for a single request before giving up on it",
+                        "default": 5000
+                    },
+                    "retry": {
+                        "$ref": "#/definitions/retry"
+                    },
                     "parser": {
                         "type": "object",
                         "properties": {
@@ -93,12 +127,150 @@ const DEPLOY_DESCRIPTOR_SCHEMA: &str = r#"
                         "type": "object",
                         "description": "Additional headers to send with the request",
                         "default": null
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "description": "How long to wait for a single request before giving up on it",
+                        "default": 5000
+                    },
+                    "retry": {
+                        "$ref": "#/definitions/retry"
                     }
                 }
+            },
+            "pre_deploy_checks": {
+                "type": "object",
+                "description": "Checks of the target that have to pass before deployed code is replaced, unless deploying with --force",
+                "properties": {
+                    "min_free_space_mb": {
+                        "type": "integer",
+                        "description": "The least free space under the deploy directory"
+                    },
+                    "max_clock_skew_s": {
+                        "type": "integer",
+                        "description": "How many seconds the clock of the target may be off from the deploying computer"
+                    },
+                    "image": {
+                        "$ref": "#/definitions/probe",
+                        "description": "Reads the OS image of the target, the output has to match the pattern"
+                    },
+                    "enabled": {
+                        "$ref": "#/definitions/probe",
+                        "description": "Reads whether the robot is enabled, the output matches the pattern while it is"
+                    }
+                }
+            },
+            "health_check": {
+                "type": "object",
+                "description": "The signal that the robot-code came up after start_cmd, exactly one of process, log_line, url or tcp_port",
+                "properties": {
+                    "process": {
+                        "type": "string",
+                        "description": "A command that succeeds while the robot-code runs, will interpolate $BINARY$ with the shell quoted name of the deployed binary, it has to keep succeeding until the timeout"
+                    },
+                    "log_line": {
+                        "type": "string",
+                        "description": "A regex matched against the lines written to log_path after start_cmd"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "A url that responds successfully once the robot-code is ready, will interpolate $ADDR$ with the address of the target"
+                    },
+                    "method": {
+                        "type": "string",
+                        "description": "The method to request the url with",
+                        "default": "GET"
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "description": "How long to wait for a single request before giving up on it",
+                        "default": 5000
+                    },
+                    "retry": {
+                        "$ref": "#/definitions/retry"
+                    },
+                    "tcp_port": {
+                        "type": "integer",
+                        "description": "A port on the target that accepts connections once the robot-code is ready"
+                    },
+                    "timeout_s": {
+                        "type": "integer",
+                        "description": "How long to wait for the signal",
+                        "default": 10
+                    },
+                    "interval_ms": {
+                        "type": "integer",
+                        "description": "How long to wait between two checks of the signal",
+                        "default": 500
+                    },
+                    "log_lines": {
+                        "type": "integer",
+                        "description": "How many lines at the end of log_path are shown when the check fails",
+                        "default": 20
+                    },
+                    "rollback": {
+                        "type": "boolean",
+                        "description": "Whether the previous binary, manifest and deploy dir are restored and started when the check fails",
+                        "default": false
+                    }
+                }
+            }
+        }
+    },
+    "definitions": {
+        "probe": {
+            "type": "object",
+            "description": "Either a command run on the target or a request to it, and a regex matched against the output",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "The command to run on the target"
+                },
+                "url": {
+                    "type": "string",
+                    "description": "The url to request, will interpolate $ADDR$ with the address of the target"
+                },
+                "method": {
+                    "type": "string",
+                    "description": "The method to request the url with",
+                    "default": "GET"
+                },
+                "timeout_ms": {
+                    "type": "integer",
+                    "description": "How long to wait for a single request before giving up on it",
+                    "default": 5000
+                },
+                "retry": {
+                    "$ref": "#/definitions/retry"
+                },
+                "pattern": {
+                    "type": "string",
+                    "description": "The regex matched against the output of the command or the body of the response"
+                }
+            }
+        },
+        "retry": {
+            "type": "object",
+            "properties": {
+                "attempts": {
+                    "type": "integer",
+                    "description": "How many times to try the request before giving up",
+                    "default": 3
+                },
+                "backoff_ms": {
+                    "type": "integer",
+                    "description": "How long to wait before the first retry, doubles after every failed attempt",
+                    "default": 500
+                },
+                "max_backoff_ms": {
+                    "type": "integer",
+                    "description": "The longest to ever wait between two attempts",
+                    "default": 4000
+                }
             }
         }
     }
-}"#;
+}"##;
 
 #[derive(Debug, Error, Clone, Copy)]
 pub enum DescriptorParseError {
@@ -122,9 +294,82 @@ pub struct Descriptor {
     pub path: String,
     pub start_cmd: String,
     pub stop_cmd: String,
+    pub rust_target: String,
     pub dep_lib_path: String,
+    pub extra_files_path: String,
+    /// The user deployed files are handed to, `None` leaves them owned by the root user.
+    pub run_as: Option<String>,
+    /// The file `start_cmd` runs the robot-code from, the deploy points it at the binary.
+    pub robot_command: Option<String>,
+    pub log_path: String,
+    pub datalog_globs: Vec<DatalogGlob>,
     pub serial_getter: SerialGetter,
     pub pinger: Pinger,
+    pub pre_deploy_checks: PreDeployChecks,
+    /// How to tell the robot-code came up after `start_cmd`, `None` doesn't wait for it.
+    pub health_check: Option<HealthCheck>,
+}
+
+/// Checks of the target before the deployed code is replaced, each one only if declared.
+#[derive(Debug, Default)]
+pub struct PreDeployChecks {
+    /// The least free space under the deploy path, in bytes.
+    pub min_free_space: Option<u64>,
+    /// How far the clock of the target may be off from the one of this computer.
+    pub max_clock_skew: Option<Duration>,
+    /// Reads the OS image, the output has to match for the image to be compatible.
+    pub image: Option<ProbeCheck>,
+    /// Reads whether the robot is enabled, the output matches while it is.
+    /// The builtin rio descriptor has none, the roboRIO has no documented way to read it.
+    pub enabled: Option<ProbeCheck>,
+}
+
+/// Something read from the target and a pattern matched against it.
+#[derive(Debug)]
+pub struct ProbeCheck {
+    pub probe: Probe,
+    pub pattern: Regex,
+}
+
+#[derive(Debug)]
+pub enum Probe {
+    /// A command run on the target, its output is read.
+    Command(String),
+    /// A request to the target, the body of the response is read.
+    Http(HttpProbe),
+}
+
+/// Waits for a signal that the robot-code came up after it was started.
+#[derive(Debug)]
+pub struct HealthCheck {
+    pub signal: HealthSignal,
+    /// How long the signal may take, or how long the process has to stay alive.
+    pub timeout: Duration,
+    /// How long to wait between two checks of the signal.
+    pub interval: Duration,
+    /// How many lines at the end of the log are shown when the check fails.
+    pub log_lines: usize,
+    /// Whether the previous binary, manifest and deploy dir are restored when the check fails.
+    pub rollback: bool,
+}
+
+#[derive(Debug)]
+pub enum HealthSignal {
+    /// A command that succeeds while the robot-code runs, `$BINARY$` is the deployed binary, quoted.
+    Process(String),
+    /// A regex matched against the lines the log gains after the start.
+    LogLine(Regex),
+    /// A request that succeeds once the robot-code is ready.
+    Http(HttpProbe),
+    /// A port of the target that accepts connections once the robot-code is ready.
+    Tcp(u16),
+}
+
+/// Where datalogs are written on the target, a directory and a glob of file names in it.
+#[derive(Debug)]
+pub struct DatalogGlob {
+    pub dir: String,
+    pub pattern: glob::Pattern,
 }
 
 #[derive(Debug)]
@@ -134,96 +379,253 @@ pub enum ParserFormat {
     REGEX,
 }
 
+#[derive(Debug, Error)]
+pub enum EndpointError {
+    #[error("{url:?} is not a valid url once $ADDR$ is interpolated")]
+    InvalidUrl { url: String },
+    #[error("Failed to connect to {url}: {source}")]
+    Connect { url: Url, source: reqwest::Error },
+    #[error("Request to {url} timed out after {timeout:?}")]
+    Timeout { url: Url, timeout: Duration },
+    #[error("Request to {url} failed with status {status}")]
+    Status { url: Url, status: StatusCode },
+    #[error("Failed to parse response from {url}: {reason}")]
+    Parse { url: Url, reason: String },
+    #[error("Response from {url} did not contain any of the configured paths")]
+    NotFound { url: Url },
+}
+impl EndpointError {
+    fn from_reqwest(url: Url, timeout: Duration, err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Self::Timeout { url, timeout }
+        } else {
+            Self::Connect { url, source: err }
+        }
+    }
+
+    /// Whether trying the same request again could reasonably succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Connect { .. } | Self::Timeout { .. } => true,
+            Self::Status { status, .. } => status.is_server_error(),
+            Self::InvalidUrl { .. } | Self::Parse { .. } | Self::NotFound { .. } => false,
+        }
+    }
+}
+
+/// How often and how patiently to retry a request to the target.
+///
+/// The first request after the radio comes up regularly fails,
+/// so endpoints are retried with an exponential backoff.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_millis(4000),
+        }
+    }
+}
+impl RetryPolicy {
+    /// The time to wait after the `attempt`th (zero based) failed attempt.
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        self.backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+
+    pub async fn run<T, F, Fut>(&self, request: F) -> Result<T, EndpointError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, EndpointError>>,
+    {
+        self.run_if(EndpointError::is_transient, request).await
+    }
+
+    /// Runs `request` until it succeeds, fails with an error that `is_transient` rejects,
+    /// or runs out of attempts.
+    pub async fn run_if<T, E, F, Fut>(
+        &self,
+        is_transient: impl Fn(&E) -> bool,
+        mut request: F,
+    ) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Ok(value) => return Ok(value),
+                Err(err) if is_transient(&err) && attempt + 1 < self.attempts => {
+                    let backoff = self.backoff_for(attempt);
+                    tracing::warn!("{}, retrying in {:?}", err, backoff);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Replaces `$ADDR$` in a descriptor url with the address of the target.
+fn interpolate_url(url: &str, addr: Ipv4Addr) -> Result<Url, EndpointError> {
+    Url::parse(&url.replace("$ADDR$", &addr.to_string())).map_err(|_| {
+        EndpointError::InvalidUrl {
+            url: url.to_owned(),
+        }
+    })
+}
+
 #[derive(Debug)]
 pub struct SerialGetter {
-    pub url: Url,
+    /// The url template, `$ADDR$` is replaced with the address of the target.
+    pub url: String,
     pub method: Method,
     pub format: ParserFormat,
     pub paths: Vec<String>,
     pub headers: HeaderMap,
+    pub timeout: Duration,
+    pub retry: RetryPolicy,
 }
 impl SerialGetter {
-    pub async fn call(&self, client: &reqwest::Client) -> Result<String, String> {
-        let mut req = client.request(self.method.clone(), self.url.clone());
-        for (key, value) in self.headers.iter() {
-            req = req.header(key, value);
+    pub async fn call(
+        &self,
+        client: &reqwest::Client,
+        addr: Ipv4Addr,
+    ) -> Result<String, EndpointError> {
+        let url = interpolate_url(&self.url, addr)?;
+        self.retry.run(|| self.call_once(client, url.clone())).await
+    }
+
+    async fn call_once(&self, client: &reqwest::Client, url: Url) -> Result<String, EndpointError> {
+        let res = client
+            .request(self.method.clone(), url.clone())
+            .headers(self.headers.clone())
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|err| EndpointError::from_reqwest(url.clone(), self.timeout, err))?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(EndpointError::Status { url, status });
         }
-        let res = req.send().await;
-        match res {
-            Ok(res) => {
-                let status = res.status();
-                if status.is_success() {
-                    match self.format {
-                        ParserFormat::JSON => {
-                            let body = res.text().await;
-                            match body {
-                                Ok(body) => self.extract_json(body),
-                                Err(err) => Err(format!("Failed to get body: {}", err)),
-                            }
-                        }
-                        _ => Err(format!("Unsupported format: {:?}", self.format)),
-                    }
-                } else {
-                    Err(format!("Failed to get body: {}", status))
-                }
-            }
-            Err(err) => Err(format!("Failed to get body: {}", err)),
+        let body = res
+            .text()
+            .await
+            .map_err(|err| EndpointError::from_reqwest(url.clone(), self.timeout, err))?;
+        match self.format {
+            ParserFormat::JSON => self.extract_json(&url, body),
+            _ => Err(EndpointError::Parse {
+                url,
+                reason: format!("unsupported format {:?}", self.format),
+            }),
         }
     }
 
-    pub fn extract_json(&self, body: String) -> Result<String, String> {
-        let json = serde_json::from_str::<serde_json::Value>(&body);
+    pub fn extract_json(&self, url: &Url, body: String) -> Result<String, EndpointError> {
+        let json = serde_json::from_str::<serde_json::Value>(&body).map_err(|err| {
+            EndpointError::Parse {
+                url: url.clone(),
+                reason: err.to_string(),
+            }
+        })?;
         let paths = self
             .paths
             .iter()
             .map(|paths| paths.split('.').collect::<Vec<&str>>())
             .collect::<Vec<Vec<&str>>>();
-        match json {
-            Ok(json) => {
-                for path in &paths {
-                    let mut json = json.clone();
-                    for attr in path {
-                        if let Some(value) = json.get(attr) {
-                            json = value.clone();
-                        } else {
-                            break;
-                        }
-                    }
-                    if let serde_json::Value::String(serial) = json {
-                        return Ok(serial);
-                    }
+        for path in &paths {
+            let mut json = json.clone();
+            for attr in path {
+                if let Some(value) = json.get(attr) {
+                    json = value.clone();
+                } else {
+                    break;
                 }
-                Err(format!("Failed to find serial in json"))
             }
-            Err(err) => Err(format!("Failed to parse json: {}", err)),
+            if let serde_json::Value::String(serial) = json {
+                return Ok(serial);
+            }
         }
+        Err(EndpointError::NotFound { url: url.clone() })
     }
 }
 
 #[derive(Debug)]
 pub struct Pinger {
-    pub url: Url,
+    /// The url template, `$ADDR$` is replaced with the address of the target.
+    pub url: String,
     pub method: Method,
     pub headers: HeaderMap,
+    pub timeout: Duration,
+    pub retry: RetryPolicy,
 }
 impl Pinger {
-    pub async fn call(&self, client: &reqwest::Client) -> Result<(), String> {
-        let mut req = client.request(self.method.clone(), self.url.clone());
-        for (key, value) in self.headers.iter() {
-            req = req.header(key, value);
+    pub async fn call(&self, client: &reqwest::Client, addr: Ipv4Addr) -> Result<(), EndpointError> {
+        let url = interpolate_url(&self.url, addr)?;
+        self.retry.run(|| self.call_once(client, url.clone())).await
+    }
+
+    async fn call_once(&self, client: &reqwest::Client, url: Url) -> Result<(), EndpointError> {
+        let res = client
+            .request(self.method.clone(), url.clone())
+            .headers(self.headers.clone())
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|err| EndpointError::from_reqwest(url.clone(), self.timeout, err))?;
+        let status = res.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(EndpointError::Status { url, status })
         }
-        let res = req.send().await;
-        match res {
-            Ok(res) => {
-                let status = res.status();
-                if status.is_success() {
-                    Ok(())
-                } else {
-                    Err(format!("Failed to ping: {}", status))
-                }
-            }
-            Err(err) => Err(format!("Failed to ping: {}", err)),
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpProbe {
+    /// The url template, `$ADDR$` is replaced with the address of the target.
+    pub url: String,
+    pub method: Method,
+    pub timeout: Duration,
+    pub retry: RetryPolicy,
+}
+impl HttpProbe {
+    /// The body of the response.
+    pub async fn call(
+        &self,
+        client: &reqwest::Client,
+        addr: Ipv4Addr,
+    ) -> Result<String, EndpointError> {
+        let url = interpolate_url(&self.url, addr)?;
+        self.retry.run(|| self.call_once(client, url.clone())).await
+    }
+
+    async fn call_once(&self, client: &reqwest::Client, url: Url) -> Result<String, EndpointError> {
+        let res = client
+            .request(self.method.clone(), url.clone())
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|err| EndpointError::from_reqwest(url.clone(), self.timeout, err))?;
+        let status = res.status();
+        if !status.is_success() {
+            return Err(EndpointError::Status { url, status });
         }
+        res.text()
+            .await
+            .map_err(|err| EndpointError::from_reqwest(url, self.timeout, err))
     }
 }
 
@@ -258,6 +660,7 @@ macro_rules! get_attr_default {
     ($jval:expr, $attr:expr, $hint:expr, $file_name:expr, $as:ident, $default:expr) => {
         $jval
             .get($attr)
+            .filter(|v| !v.is_null())
             .unwrap_or(&serde_json::Value::from($default.to_owned()))
             .$as()
             .ok_or(DescriptorParseError::ParseAttribute {
@@ -269,6 +672,33 @@ macro_rules! get_attr_default {
     };
 }
 
+/// The descriptors shipped with cargo-frc, as `(name, file name, contents)`.
+const BUILTIN_DESCRIPTORS: [(&str, &str, &str); 1] = [(
+    "rio",
+    "rio.json",
+    include_str!("../../deploy_descriptors/rio.json"),
+)];
+
+/// Loads one of the descriptors shipped with cargo-frc by name.
+pub fn builtin_descriptor(name: &str) -> Option<Result<Descriptor, DescriptorParseError>> {
+    let (_, file, contents) = BUILTIN_DESCRIPTORS
+        .iter()
+        .find(|(builtin, _, _)| *builtin == name)?;
+    let jval = serde_json::from_str(contents).expect("builtin descriptors are valid json");
+    Some(parse_descriptor(jval, file))
+}
+
+/// Loads every descriptor shipped with cargo-frc, as `(name, descriptor)`.
+pub fn builtin_descriptors() -> Vec<(&'static str, Result<Descriptor, DescriptorParseError>)> {
+    BUILTIN_DESCRIPTORS
+        .iter()
+        .map(|(name, file, contents)| {
+            let jval = serde_json::from_str(contents).expect("builtin descriptors are valid json");
+            (*name, parse_descriptor(jval, file))
+        })
+        .collect()
+}
+
 pub fn parse_descriptor(
     jval: serde_json::Value,
     file: &'static str,
@@ -310,6 +740,14 @@ pub fn parse_descriptor(
         file,
         as_str
     );
+    let rust_target = get_attr_default!(
+        jval,
+        "rust_target",
+        "the rust target triple to build robot-code for",
+        file,
+        as_str,
+        "arm-unknown-linux-gnueabi"
+    );
     let dep_lib_path = get_attr_default!(
         jval,
         "dep_lib_path",
@@ -318,6 +756,59 @@ pub fn parse_descriptor(
         as_str,
         "./lib"
     );
+    let extra_files_path = get_attr_default!(
+        jval,
+        "extra_files_path",
+        "the path to the extra files relative to the deploy directory on the target",
+        file,
+        as_str,
+        "./deploy"
+    );
+    let run_as = jval
+        .get("run_as")
+        .filter(|v| !v.is_null())
+        .map(|v| {
+            v.as_str()
+                .map(|s| s.to_owned())
+                .ok_or(DescriptorParseError::ParseAttribute {
+                    name: "run_as",
+                    hint: "the user the robot-code runs as on the target",
+                    file_name: file,
+                })
+        })
+        .transpose()?;
+    let robot_command = jval
+        .get("robot_command")
+        .filter(|v| !v.is_null())
+        .map(|v| {
+            v.as_str()
+                .map(|s| s.to_owned())
+                .ok_or(DescriptorParseError::ParseAttribute {
+                    name: "robot_command",
+                    hint: "the unix path of the file start_cmd runs the robot-code from",
+                    file_name: file,
+                })
+        })
+        .transpose()?;
+    let log_path = get_attr_default!(
+        jval,
+        "log_path",
+        "the unix path to the console log of the robot-code on the target",
+        file,
+        as_str,
+        "~/FRC_UserProgram.log"
+    );
+    let datalog_globs = get_attr_default!(
+        jval,
+        "datalog_globs",
+        "an array of unix paths, globs are allowed in the file name",
+        file,
+        as_array,
+        Vec::<serde_json::Value>::new()
+    )
+    .iter()
+    .map(|path| parse_datalog_glob(path, file))
+    .collect::<Result<Vec<DatalogGlob>, DescriptorParseError>>()?;
     let serial_getter = parse_serial_getter(
         get_attr!(
             jval,
@@ -336,15 +827,53 @@ pub fn parse_descriptor(
         ),
         file,
     )?;
+    let pre_deploy_checks = match jval.get("pre_deploy_checks").filter(|v| !v.is_null()) {
+        Some(checks) => parse_pre_deploy_checks(checks, file)?,
+        None => PreDeployChecks::default(),
+    };
+    let health_check = jval
+        .get("health_check")
+        .filter(|v| !v.is_null())
+        .map(|v| parse_health_check(v, file))
+        .transpose()?;
     Ok(Descriptor {
         root_user,
         root_password,
         path,
         start_cmd,
         stop_cmd,
+        rust_target,
         dep_lib_path,
+        extra_files_path,
+        run_as,
+        robot_command,
+        log_path,
+        datalog_globs,
         serial_getter,
         pinger,
+        pre_deploy_checks,
+        health_check,
+    })
+}
+
+fn parse_datalog_glob(
+    jval: &serde_json::Value,
+    file: &'static str,
+) -> Result<DatalogGlob, DescriptorParseError> {
+    let err = DescriptorParseError::ParseAttribute {
+        name: "datalog_globs",
+        hint: "an array of unix paths, globs are allowed in the file name",
+        file_name: file,
+    };
+    let path = jval.as_str().ok_or(err)?;
+    let (dir, name) = path.rsplit_once('/').ok_or(err)?;
+    if name.is_empty() || glob::Pattern::escape(dir) != dir {
+        return Err(err);
+    }
+    let pattern = glob::Pattern::new(name).map_err(|_| err)?;
+    Ok(DatalogGlob {
+        dir: if dir.is_empty() { "/" } else { dir }.to_owned(),
+        pattern,
     })
 }
 
@@ -359,10 +888,12 @@ pub fn parse_serial_getter(
         file,
         as_str
     );
-    let url = Url::parse(&url).map_err(|_| DescriptorParseError::ParseAttribute {
-        name: "url",
-        hint: "a string that can be parsed as a url",
-        file_name: file,
+    interpolate_url(&url, Ipv4Addr::LOCALHOST).map_err(|_| {
+        DescriptorParseError::ParseAttribute {
+            name: "url",
+            hint: "a string that can be parsed as a url",
+            file_name: file,
+        }
     })?;
     let method = get_attr_default!(
         jval,
@@ -389,7 +920,13 @@ pub fn parse_serial_getter(
             })
         }
     };
-    let format = get_attr!(jval, "format", "the format of the keys", file, as_str);
+    let parser = get_attr!(
+        jval,
+        "parser",
+        "an object with the format and paths of the serial number in the response",
+        file
+    );
+    let format = get_attr!(parser, "format", "the format of the keys", file, as_str);
     let format = match format.as_str() {
         "json" => ParserFormat::JSON,
         "xml" => ParserFormat::XML,
@@ -403,7 +940,7 @@ pub fn parse_serial_getter(
         }
     };
     let paths = get_attr!(
-        jval,
+        parser,
         "paths",
         "The keys to the serial number in the response",
         file,
@@ -461,12 +998,16 @@ pub fn parse_serial_getter(
             Ok((header, value))
         })
         .collect::<Result<HeaderMap, DescriptorParseError>>()?;
+    let timeout = parse_timeout(&jval, file)?;
+    let retry = parse_retry(&jval, file)?;
     Ok(SerialGetter {
         url,
         method,
         format,
         paths,
         headers,
+        timeout,
+        retry,
     })
 }
 
@@ -481,10 +1022,12 @@ pub fn parse_pinger(
         file,
         as_str
     );
-    let url = Url::parse(&url).map_err(|_| DescriptorParseError::ParseAttribute {
-        name: "url",
-        hint: "a string that can be parsed as a url",
-        file_name: file,
+    interpolate_url(&url, Ipv4Addr::LOCALHOST).map_err(|_| {
+        DescriptorParseError::ParseAttribute {
+            name: "url",
+            hint: "a string that can be parsed as a url",
+            file_name: file,
+        }
     })?;
     let method = get_attr_default!(
         jval,
@@ -552,9 +1095,291 @@ pub fn parse_pinger(
             Ok((header, value))
         })
         .collect::<Result<HeaderMap, DescriptorParseError>>()?;
+    let timeout = parse_timeout(&jval, file)?;
+    let retry = parse_retry(&jval, file)?;
     Ok(Pinger {
         url,
         method,
         headers,
+        timeout,
+        retry,
+    })
+}
+
+pub fn parse_pre_deploy_checks(
+    jval: &serde_json::Value,
+    file: &'static str,
+) -> Result<PreDeployChecks, DescriptorParseError> {
+    let optional_u64 = |name: &'static str| {
+        jval.get(name)
+            .filter(|v| !v.is_null())
+            .map(|v| {
+                v.as_u64().ok_or(DescriptorParseError::ParseAttribute {
+                    name,
+                    hint: "a positive number",
+                    file_name: file,
+                })
+            })
+            .transpose()
+    };
+    let min_free_space = optional_u64("min_free_space_mb")?.map(|mb| mb * 1024 * 1024);
+    let max_clock_skew = optional_u64("max_clock_skew_s")?.map(Duration::from_secs);
+    let image = jval
+        .get("image")
+        .filter(|v| !v.is_null())
+        .map(|v| parse_probe_check(v, "image", file))
+        .transpose()?;
+    let enabled = jval
+        .get("enabled")
+        .filter(|v| !v.is_null())
+        .map(|v| parse_probe_check(v, "enabled", file))
+        .transpose()?;
+    Ok(PreDeployChecks {
+        min_free_space,
+        max_clock_skew,
+        image,
+        enabled,
     })
 }
+
+fn parse_probe_check(
+    jval: &serde_json::Value,
+    name: &'static str,
+    file: &'static str,
+) -> Result<ProbeCheck, DescriptorParseError> {
+    let err = DescriptorParseError::ParseAttribute {
+        name,
+        hint: "an object with a `command` or a `url`, and a `pattern` regex",
+        file_name: file,
+    };
+    let pattern = get_attr!(jval, "pattern", "a regex", file, as_str);
+    let pattern = Regex::new(&pattern).map_err(|_| DescriptorParseError::ParseAttribute {
+        name: "pattern",
+        hint: "a regex",
+        file_name: file,
+    })?;
+    let probe = match (jval.get("command"), jval.get("url")) {
+        (Some(command), None) => Probe::Command(command.as_str().ok_or(err)?.to_owned()),
+        (None, Some(url)) => Probe::Http(parse_http_probe(jval, url.as_str().ok_or(err)?, file)?),
+        _ => return Err(err),
+    };
+    Ok(ProbeCheck { probe, pattern })
+}
+
+fn parse_http_probe(
+    jval: &serde_json::Value,
+    url: &str,
+    file: &'static str,
+) -> Result<HttpProbe, DescriptorParseError> {
+    interpolate_url(url, Ipv4Addr::LOCALHOST).map_err(|_| {
+        DescriptorParseError::ParseAttribute {
+            name: "url",
+            hint: "a string that can be parsed as a url",
+            file_name: file,
+        }
+    })?;
+    let method = get_attr_default!(
+        jval,
+        "method",
+        "the method to request the url with",
+        file,
+        as_str,
+        "GET"
+    );
+    let method = Method::from_bytes(method.as_bytes()).map_err(|_| {
+        DescriptorParseError::ParseAttribute {
+            name: "method",
+            hint: "a string that can be parsed as a method",
+            file_name: file,
+        }
+    })?;
+    Ok(HttpProbe {
+        url: url.to_owned(),
+        method,
+        timeout: parse_timeout(jval, file)?,
+        retry: parse_retry(jval, file)?,
+    })
+}
+
+pub fn parse_health_check(
+    jval: &serde_json::Value,
+    file: &'static str,
+) -> Result<HealthCheck, DescriptorParseError> {
+    let err = DescriptorParseError::ParseAttribute {
+        name: "health_check",
+        hint: "an object with exactly one of `process`, `log_line`, `url` or `tcp_port`",
+        file_name: file,
+    };
+    let declared = ["process", "log_line", "url", "tcp_port"]
+        .into_iter()
+        .filter_map(|name| jval.get(name).filter(|v| !v.is_null()).map(|v| (name, v)))
+        .collect::<Vec<_>>();
+    let signal = match declared.as_slice() {
+        [("process", command)] => HealthSignal::Process(command.as_str().ok_or(err)?.to_owned()),
+        [("log_line", pattern)] => {
+            let pattern = pattern.as_str().ok_or(err)?;
+            HealthSignal::LogLine(Regex::new(pattern).map_err(|_| {
+                DescriptorParseError::ParseAttribute {
+                    name: "log_line",
+                    hint: "a regex",
+                    file_name: file,
+                }
+            })?)
+        }
+        [("url", url)] => {
+            HealthSignal::Http(parse_http_probe(jval, url.as_str().ok_or(err)?, file)?)
+        }
+        [("tcp_port", port)] => HealthSignal::Tcp(
+            port.as_u64()
+                .and_then(|port| u16::try_from(port).ok())
+                .ok_or(DescriptorParseError::ParseAttribute {
+                    name: "tcp_port",
+                    hint: "a port number",
+                    file_name: file,
+                })?,
+        ),
+        _ => return Err(err),
+    };
+    let timeout_s = get_attr_default!(
+        jval,
+        "timeout_s",
+        "a positive number of seconds",
+        file,
+        as_u64,
+        10u64
+    );
+    let interval_ms = get_attr_default!(
+        jval,
+        "interval_ms",
+        "a positive number of milliseconds",
+        file,
+        as_u64,
+        500u64
+    );
+    let log_lines = get_attr_default!(
+        jval,
+        "log_lines",
+        "a positive number of lines",
+        file,
+        as_u64,
+        20u64
+    );
+    let rollback = get_attr_default!(
+        jval,
+        "rollback",
+        "whether to restore the previous binary",
+        file,
+        as_bool,
+        false
+    );
+    Ok(HealthCheck {
+        signal,
+        timeout: Duration::from_secs(timeout_s),
+        interval: Duration::from_millis(interval_ms),
+        log_lines: log_lines as usize,
+        rollback,
+    })
+}
+
+fn parse_timeout(
+    jval: &serde_json::Value,
+    file: &'static str,
+) -> Result<Duration, DescriptorParseError> {
+    let timeout_ms = get_attr_default!(
+        jval,
+        "timeout_ms",
+        "a positive number of milliseconds",
+        file,
+        as_u64,
+        5000u64
+    );
+    Ok(Duration::from_millis(timeout_ms))
+}
+
+pub fn parse_retry(
+    jval: &serde_json::Value,
+    file: &'static str,
+) -> Result<RetryPolicy, DescriptorParseError> {
+    let default = RetryPolicy::default();
+    let Some(retry) = jval.get("retry").filter(|v| !v.is_null()) else {
+        return Ok(default);
+    };
+    let attempts = get_attr_default!(
+        retry,
+        "attempts",
+        "a positive number of attempts",
+        file,
+        as_u64,
+        u64::from(default.attempts)
+    );
+    let attempts = u32::try_from(attempts)
+        .ok()
+        .filter(|attempts| *attempts > 0)
+        .ok_or(DescriptorParseError::ParseAttribute {
+            name: "attempts",
+            hint: "a positive number of attempts",
+            file_name: file,
+        })?;
+    let backoff_ms = get_attr_default!(
+        retry,
+        "backoff_ms",
+        "a positive number of milliseconds",
+        file,
+        as_u64,
+        default.backoff.as_millis() as u64
+    );
+    let max_backoff_ms = get_attr_default!(
+        retry,
+        "max_backoff_ms",
+        "a positive number of milliseconds",
+        file,
+        as_u64,
+        default.max_backoff.as_millis() as u64
+    );
+    Ok(RetryPolicy {
+        attempts,
+        backoff: Duration::from_millis(backoff_ms),
+        max_backoff: Duration::from_millis(max_backoff_ms),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_rio_descriptor() {
+        let jval = serde_json::from_str(include_str!("../../deploy_descriptors/rio.json")).unwrap();
+        let descriptor = parse_descriptor(jval, "rio.json").unwrap();
+        assert_eq!(descriptor.serial_getter.paths, vec!["system.serialNumber"]);
+        assert_eq!(descriptor.serial_getter.retry.attempts, 5);
+        assert_eq!(descriptor.pinger.timeout, Duration::from_millis(2000));
+        assert_eq!(descriptor.run_as.as_deref(), Some("lvuser"));
+        assert_eq!(
+            descriptor.robot_command.as_deref(),
+            Some("/home/lvuser/robotCommand")
+        );
+        assert_eq!(descriptor.datalog_globs[0].dir, "/home/lvuser/logs");
+        assert!(descriptor.datalog_globs[0].pattern.matches("FRC_20240301_120000.wpilog"));
+        let checks = &descriptor.pre_deploy_checks;
+        assert_eq!(checks.min_free_space, Some(50 * 1024 * 1024));
+        assert!(checks.enabled.is_none());
+        let image = checks.image.as_ref().unwrap();
+        assert!(matches!(image.probe, Probe::Command(_)));
+        assert!(image.pattern.is_match("IMAGEVERSION = \"FRC_roboRIO2_2024_v2.1\""));
+        assert!(image.pattern.is_match("IMAGEVERSION = \"FRC_roboRIO_2025_v1.2\""));
+        assert!(!image.pattern.is_match("IMAGEVERSION = \"roboRIO_2024_v2.1\""));
+        let health = descriptor.health_check.as_ref().unwrap();
+        assert!(matches!(&health.signal, HealthSignal::Process(cmd) if cmd.contains("$BINARY$")));
+        assert_eq!(health.timeout, Duration::from_secs(10));
+        assert!(!health.rollback);
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.backoff_for(0), Duration::from_millis(500));
+        assert_eq!(retry.backoff_for(2), Duration::from_millis(2000));
+        assert_eq!(retry.backoff_for(40), Duration::from_millis(4000));
+    }
+}
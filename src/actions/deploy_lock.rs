@@ -0,0 +1,81 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::deploy_manifest;
+
+/// The file name of the lock, written into the descriptor's deploy path on the target
+/// while a deploy is running.
+pub const DEPLOY_LOCK: &str = "frc-deploy.lock";
+
+/// How many minutes a lock holds, an older one was left behind by a deploy that never finished.
+pub const STALE_AFTER_MINUTES: i64 = 10;
+
+/// Who is deploying to the target, other deploys refuse to start while it's held.
+///
+/// The lock is advisory, it only keeps out other deploys of cargo-frc.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeployLock {
+    pub host: String,
+    pub user: String,
+    /// The commit being deployed, if it's known.
+    pub sha: Option<String>,
+    /// When the lock was taken, in RFC 3339.
+    pub since: String,
+}
+impl DeployLock {
+    /// A lock for a deploy of `sha` from this computer, taken now.
+    pub fn new(sha: Option<String>) -> Self {
+        Self {
+            host: deploy_manifest::local_host(),
+            user: deploy_manifest::local_user(),
+            sha,
+            since: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// How long ago the lock was taken, `None` if `since` can't be read.
+    pub fn age(&self, now: DateTime<Utc>) -> Option<Duration> {
+        DateTime::parse_from_rfc3339(&self.since)
+            .ok()
+            .map(|since| now.signed_duration_since(since))
+    }
+
+    /// Whether the lock was left behind, taken too long ago or at a time that can't be read.
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        match self.age(now) {
+            Some(age) => age > Duration::minutes(STALE_AFTER_MINUTES),
+            None => true,
+        }
+    }
+
+    /// Who holds the lock, like `user@host deploying 1a2b3c4`.
+    pub fn holder(&self) -> String {
+        match &self.sha {
+            Some(sha) => format!(
+                "{}@{} deploying {}",
+                self.user,
+                self.host,
+                &sha[..sha.len().min(7)]
+            ),
+            None => format!("{}@{}", self.user, self.host),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stale_lock() {
+        let now = Utc::now();
+        let mut lock = DeployLock::new(Some("1a2b3c4d5e6f".to_owned()));
+        assert!(!lock.is_stale(now));
+        assert!(lock.holder().ends_with("deploying 1a2b3c4"));
+
+        lock.since = (now - Duration::minutes(STALE_AFTER_MINUTES + 1)).to_rfc3339();
+        assert!(lock.is_stale(now));
+        lock.since = "yesterday".to_owned();
+        assert!(lock.is_stale(now));
+    }
+}
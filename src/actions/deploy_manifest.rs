@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use super::git_commit::Provenance;
+
+/// The file name of the manifest, written into the descriptor's deploy path on the target.
+pub const DEPLOY_MANIFEST: &str = "frc-deploy.json";
+
+/// A record of what was deployed, when and by whom, kept on the target next to the binary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployManifest {
+    pub package: String,
+    /// The file name of the deployed binary.
+    pub binary: String,
+    pub team: u16,
+    pub robot: Option<String>,
+    /// When the build was made, in RFC 3339.
+    pub build_time: String,
+    /// The `--cfg` flags the binary was built with.
+    pub cfgs: Vec<String>,
+    /// `user@host` of whoever deployed.
+    pub deployed_by: String,
+    pub git: Provenance,
+    /// The competition the deploy was made at, if any.
+    pub competition: Option<String>,
+    /// The commit made on `comp/<competition>` for this deploy, if any.
+    pub competition_commit: Option<String>,
+}
+
+/// `user@host` of the current user, as far as the environment tells.
+pub fn deploying_user() -> String {
+    format!("{}@{}", local_user(), local_host())
+}
+
+/// The name of the current user, as far as the environment tells.
+pub fn local_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+/// The hostname of this computer, as far as the environment tells.
+pub fn local_host() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|host| host.trim().to_owned())
+        })
+        .unwrap_or_else(|| "unknown".to_owned())
+}
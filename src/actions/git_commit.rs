@@ -1,29 +1,692 @@
-use gix::{prelude::*, Repository};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use gix::{
+    bstr::BString,
+    objs::{tree, Kind, WriteTo},
+    refs::{transaction::PreviousValue, Target},
+    worktree::stack::state::ignore::Source,
+    AttributeStack, ObjectId, Repository,
+};
+use serde::{Deserialize, Serialize};
 
 const MESSAGE: &str = "cargo-frc automated commit";
 
-/// commit all files in the repository.
-// pub fn make_commit(repo: &Repository, comp_acronymn: Option<String>) {
-//     //get entry for every changed file
-//     let mut index = repo.index().unwrap();
-//     let mut changed_files = Vec::new();
-//     for entry in index.entries().iter() {
-//         if entry.stage() != 0 {
-//             changed_files.push(entry.path().unwrap().to_str().unwrap().to_string());
-//         }
-//     }
-// }
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GitError {
+    #[error("Failed to open the git repository")]
+    Open(#[source] BoxError),
+    #[error("The git repository has no work tree")]
+    Bare,
+    #[error("Failed to read the git index")]
+    Index(#[source] BoxError),
+    #[error("Failed to read the ignore rules of the repository")]
+    Ignore(#[source] BoxError),
+    #[error("Failed to run the git filters on {path:?}")]
+    Filter { path: PathBuf, source: BoxError },
+    #[error("Failed to read {path:?}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to write a git object")]
+    Write(#[source] BoxError),
+    #[error("Failed to resolve {name}")]
+    Reference { name: String, source: BoxError },
+    #[error("Failed to commit to {branch}")]
+    Commit { branch: String, source: BoxError },
+}
+
+/// A file or directory of the tree being assembled from the work tree.
+enum Node {
+    Leaf(tree::EntryMode, ObjectId),
+    Dir(BTreeMap<BString, Node>),
+}
+
+/// Opens the repository containing the current directory.
+pub fn open_repo() -> Result<Repository, GitError> {
+    let cwd = std::env::current_dir().map_err(|err| GitError::Open(err.into()))?;
+    gix::discover(cwd).map_err(|err| GitError::Open(err.into()))
+}
+
+/// Whether objects of a snapshot are written to the object database or only hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Snapshot {
+    Write,
+    HashOnly,
+}
+
+/// Builds a tree of the current content of the work tree, without touching the index.
+///
+/// That is every tracked file and every untracked file that isn't ignored,
+/// so the tree is what a build of the work tree compiles.
+/// Content goes through git's filters (`core.autocrlf`, `.gitattributes`) as `git add` would,
+/// and tracked files whose stat still matches the index aren't read at all, as in `git status`.
+/// Tracked files that were deleted from the work tree are left out.
+/// With [`Snapshot::HashOnly`] nothing is written and only the id of the tree is computed.
+pub fn snapshot_work_tree(repo: &Repository, snapshot: Snapshot) -> Result<ObjectId, GitError> {
+    let work_dir = repo.work_dir().ok_or(GitError::Bare)?.to_path_buf();
+    let index = repo
+        .index_or_empty()
+        .map_err(|err| GitError::Index(err.into()))?;
+    let (pipeline, _) = repo.filter_pipeline(None).map_err(|err| GitError::Filter {
+        path: work_dir.clone(),
+        source: err.into(),
+    })?;
+    let mut excludes = repo
+        .excludes(&index, None, Source::WorktreeThenIdMappingIfNotSkipped)
+        .map_err(|err| GitError::Ignore(err.into()))?;
+    let tracked: BTreeSet<BString> = index
+        .entries()
+        .iter()
+        .map(|entry| entry.path(&index).to_owned())
+        .collect();
+    let mut walk = Walk {
+        repo,
+        snapshot,
+        work_dir: &work_dir,
+        index: &index,
+        tracked: &tracked,
+        pipeline,
+        excludes: &mut excludes,
+    };
+
+    let mut root = BTreeMap::new();
+    let stat_options = gix::index::entry::stat::Options::default();
+    for entry in index.entries() {
+        if entry.stage() != 0 {
+            continue;
+        }
+        let Some(mode) = entry.mode.to_tree_entry_mode() else {
+            continue;
+        };
+        let rela_path = entry.path(&index);
+        let path = work_dir.join(gix::path::from_bstr(rela_path));
+        let oid = match mode {
+            // submodules are recorded with the commit the index points at
+            tree::EntryMode::Commit => entry.id,
+            _ if unchanged(entry, &index, &path, stat_options) => entry.id,
+            _ => match walk.blob(&path, mode)? {
+                Some(oid) => oid,
+                None => continue,
+            },
+        };
+        insert(&mut root, rela_path.split(|b| *b == b'/'), mode, oid);
+    }
+
+    walk.add_untracked(&work_dir, &mut root)?;
+    write_tree(repo, snapshot, root)
+}
+
+/// Whether the stat of the file at `path` still matches its index entry,
+/// in which case the content is that of the index too.
+///
+/// Like git, a file changed within the same tick the index was written in isn't trusted.
+fn unchanged(
+    entry: &gix::index::Entry,
+    index: &gix::index::State,
+    path: &Path,
+    options: gix::index::entry::stat::Options,
+) -> bool {
+    let Ok(meta) = gix::index::fs::Metadata::from_path_no_follow(path) else {
+        return false;
+    };
+    let Ok(stat) = gix::index::entry::Stat::from_fs(&meta) else {
+        return false;
+    };
+    entry.stat.matches(&stat, options) && !entry.stat.is_racy(index.timestamp(), options)
+}
+
+/// Reads files of the work tree into the tree being assembled.
+struct Walk<'a, 'repo> {
+    repo: &'repo Repository,
+    snapshot: Snapshot,
+    work_dir: &'a Path,
+    index: &'a gix::index::State,
+    tracked: &'a BTreeSet<BString>,
+    pipeline: gix::filter::Pipeline<'repo>,
+    excludes: &'a mut AttributeStack<'repo>,
+}
+
+impl Walk<'_, '_> {
+    /// Adds the files below `dir` that are neither tracked nor ignored.
+    fn add_untracked(
+        &mut self,
+        dir: &Path,
+        root: &mut BTreeMap<BString, Node>,
+    ) -> Result<(), GitError> {
+        let read_err = |source| GitError::Read {
+            path: dir.to_path_buf(),
+            source,
+        };
+        for entry in std::fs::read_dir(dir).map_err(read_err)? {
+            let entry = entry.map_err(read_err)?;
+            let path = entry.path();
+            let file_type = entry.file_type().map_err(read_err)?;
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            let rela_path = path
+                .strip_prefix(self.work_dir)
+                .expect("walked below the work tree");
+            let rela_bstr =
+                gix::path::to_unix_separators_on_windows(gix::path::into_bstr(rela_path));
+            let is_dir = file_type.is_dir();
+            if !is_dir && self.tracked.contains(&*rela_bstr) {
+                continue;
+            }
+            let excluded = self
+                .excludes
+                .at_path(rela_path, Some(is_dir))
+                .map_err(|source| GitError::Read {
+                    path: path.clone(),
+                    source,
+                })?
+                .is_excluded();
+            if excluded {
+                continue;
+            }
+            if is_dir {
+                // nested repositories are only part of the tree as tracked submodules
+                if !path.join(".git").exists() {
+                    self.add_untracked(&path, root)?;
+                }
+                continue;
+            }
+
+            let mode = if file_type.is_symlink() {
+                tree::EntryMode::Link
+            } else if is_executable(&entry.metadata().map_err(read_err)?) {
+                tree::EntryMode::BlobExecutable
+            } else {
+                tree::EntryMode::Blob
+            };
+            if let Some(oid) = self.blob(&path, mode)? {
+                insert(root, rela_bstr.split(|b| *b == b'/'), mode, oid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores the content of the file at `path` as git would, `None` if it no longer exists.
+    fn blob(&mut self, path: &Path, mode: tree::EntryMode) -> Result<Option<ObjectId>, GitError> {
+        let read_err = |source| GitError::Read {
+            path: path.to_path_buf(),
+            source,
+        };
+        let data = if mode == tree::EntryMode::Link {
+            match std::fs::read_link(path) {
+                Ok(target) => gix::path::into_bstr(target).into_owned().into(),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(source) => return Err(read_err(source)),
+            }
+        } else {
+            let file = match std::fs::File::open(path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(source) => return Err(read_err(source)),
+            };
+            let rela_path = path
+                .strip_prefix(self.work_dir)
+                .expect("below the work tree");
+            let mut filtered = self
+                .pipeline
+                .convert_to_git(file, rela_path, self.index)
+                .map_err(|err| GitError::Filter {
+                    path: path.to_path_buf(),
+                    source: err.into(),
+                })?;
+            let mut data = Vec::new();
+            filtered.read_to_end(&mut data).map_err(read_err)?;
+            data
+        };
+        store(self.repo, self.snapshot, Kind::Blob, &data).map(Some)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+fn store(
+    repo: &Repository,
+    snapshot: Snapshot,
+    kind: Kind,
+    data: &[u8],
+) -> Result<ObjectId, GitError> {
+    match snapshot {
+        Snapshot::HashOnly => Ok(gix::objs::compute_hash(repo.object_hash(), kind, data)),
+        Snapshot::Write => repo
+            .write_buf(kind, data)
+            .map(|id| id.detach())
+            .map_err(|err| GitError::Write(err.into())),
+    }
+}
+
+fn insert<'a>(
+    dir: &mut BTreeMap<BString, Node>,
+    mut components: impl Iterator<Item = &'a [u8]>,
+    mode: tree::EntryMode,
+    oid: ObjectId,
+) {
+    let Some(name) = components.next() else {
+        return;
+    };
+    let mut rest = components.peekable();
+    if rest.peek().is_none() {
+        dir.insert(name.into(), Node::Leaf(mode, oid));
+        return;
+    }
+    let child = dir
+        .entry(name.into())
+        .or_insert_with(|| Node::Dir(BTreeMap::new()));
+    if let Node::Dir(child) = child {
+        insert(child, rest, mode, oid);
+    }
+}
+
+fn write_tree(
+    repo: &Repository,
+    snapshot: Snapshot,
+    dir: BTreeMap<BString, Node>,
+) -> Result<ObjectId, GitError> {
+    let mut entries = Vec::with_capacity(dir.len());
+    for (filename, node) in dir {
+        let (mode, oid) = match node {
+            Node::Leaf(mode, oid) => (mode, oid),
+            Node::Dir(children) => (
+                tree::EntryMode::Tree,
+                write_tree(repo, snapshot, children)?,
+            ),
+        };
+        entries.push(tree::Entry {
+            mode,
+            filename,
+            oid,
+        });
+    }
+    // git orders directories as if their name ended with a slash
+    entries.sort();
+    let mut buf = Vec::new();
+    gix::objs::Tree { entries }
+        .write_to(&mut buf)
+        .map_err(|err| GitError::Write(err.into()))?;
+    store(repo, snapshot, Kind::Tree, &buf)
+}
+
+fn resolve(repo: &Repository, name: &str) -> Result<Option<ObjectId>, GitError> {
+    let err = |err: BoxError| GitError::Reference {
+        name: name.to_owned(),
+        source: err,
+    };
+    // HEAD exists on an unborn branch too, it just doesn't point at a commit yet
+    if name == "HEAD" {
+        return repo
+            .head()
+            .map_err(|e| err(e.into()))?
+            .try_into_peeled_id()
+            .map(|id| id.map(|id| id.detach()))
+            .map_err(|e| err(e.into()));
+    }
+    let reference = repo.try_find_reference(name).map_err(|e| err(e.into()))?;
+    reference
+        .map(|reference| {
+            reference
+                .into_fully_peeled_id()
+                .map(|id| id.detach())
+                .map_err(|e| err(e.into()))
+        })
+        .transpose()
+}
+
+/// A commit of the deployed code, written before the deploy
+/// and only put on its competition branch once the deploy succeeded.
+pub struct DeployCommit {
+    repo: Repository,
+    pub id: ObjectId,
+    branch: String,
+    tip: Option<ObjectId>,
+}
+
+/// Writes a commit of `tree` for `comp/<acronym>`, leaving HEAD, the index and the branch alone.
+///
+/// The commit's first parent is the previous commit on the competition branch,
+/// the second is HEAD, so the branch is a history of every deploy at the event
+/// that still links back to the code it was made from.
+pub fn prepare_commit(
+    repo: Repository,
+    acronym: &str,
+    robot: Option<&str>,
+    time: DateTime<Local>,
+    tree: ObjectId,
+) -> Result<DeployCommit, GitError> {
+    let branch = format!("refs/heads/comp/{}", acronym);
+    let commit_err = |source: BoxError| GitError::Commit {
+        branch: branch.clone(),
+        source,
+    };
+
+    let head = resolve(&repo, "HEAD")?;
+    let tip = resolve(&repo, &branch)?;
+    let mut parents = tip.into_iter().collect::<Vec<_>>();
+    if let Some(head) = head.filter(|head| Some(*head) != tip) {
+        parents.push(head);
+    }
+
+    let message = format!(
+        "{MESSAGE}\n\ncompetition: {acronym}\nrobot: {}\nmatch time: {}\n",
+        robot.unwrap_or("unknown"),
+        time.to_rfc3339(),
+    );
+    let committer = repo
+        .committer()
+        .ok_or_else(|| commit_err("no committer is configured".into()))?
+        .map_err(|err| commit_err(err.into()))?
+        .to_owned();
+    let author = match repo.author() {
+        Some(author) => author.map_err(|err| commit_err(err.into()))?.to_owned(),
+        None => committer.clone(),
+    };
+    let commit = gix::objs::Commit {
+        tree,
+        parents: parents.into(),
+        author,
+        committer,
+        encoding: None,
+        message: message.into(),
+        extra_headers: Vec::new(),
+    };
+    let id = repo
+        .write_object(&commit)
+        .map_err(|err| commit_err(err.into()))?
+        .detach();
+    Ok(DeployCommit {
+        repo,
+        id,
+        branch,
+        tip,
+    })
+}
+
+impl DeployCommit {
+    /// The short name of the competition branch.
+    pub fn branch(&self) -> &str {
+        self.branch.trim_start_matches("refs/heads/")
+    }
+
+    /// Moves the competition branch to the commit,
+    /// failing if another deploy moved it since the commit was prepared.
+    pub fn publish(&self) -> Result<(), GitError> {
+        let constraint = match self.tip {
+            Some(tip) => PreviousValue::MustExistAndMatch(Target::Peeled(tip)),
+            None => PreviousValue::MustNotExist,
+        };
+        self.repo
+            .reference(self.branch.as_str(), self.id, constraint, MESSAGE)
+            .map(|_| ())
+            .map_err(|err| GitError::Commit {
+                branch: self.branch.clone(),
+                source: err.into(),
+            })
+    }
+}
+
+/// The provenance of the code a deploy builds
+/// and, during the competition `acronym`, the commit of exactly that code.
+///
+/// Without a competition the provenance is best-effort as for any build,
+/// during one a work tree that can't be committed fails the deploy.
+pub fn deploy_snapshot(
+    acronym: Option<&str>,
+    robot: Option<&str>,
+) -> Result<(Provenance, Option<DeployCommit>), GitError> {
+    let Some(acronym) = acronym else {
+        return Ok((Provenance::current_or_unknown(), None));
+    };
+    let repo = open_repo()?;
+    let tree = snapshot_work_tree(&repo, Snapshot::Write)?;
+    let provenance = provenance_of(&repo, tree)?;
+    let commit = prepare_commit(repo, acronym, robot, Local::now(), tree)?;
+    Ok((provenance, Some(commit)))
+}
+
+/// How many commits `descendant` is ahead of `ancestor`,
+/// `None` if `ancestor` is not in the history of `descendant` or unknown to the repository.
+pub fn commits_between(
+    repo: &Repository,
+    ancestor: &str,
+    descendant: ObjectId,
+) -> Result<Option<usize>, GitError> {
+    let Ok(ancestor) = ObjectId::from_hex(ancestor.as_bytes()) else {
+        return Ok(None);
+    };
+    let walk = repo
+        .rev_walk([descendant])
+        .all()
+        .map_err(|err| GitError::Reference {
+            name: descendant.to_string(),
+            source: err.into(),
+        })?;
+    for (count, info) in walk.enumerate() {
+        let info = info.map_err(|err| GitError::Reference {
+            name: descendant.to_string(),
+            source: err.into(),
+        })?;
+        if info.id == ancestor {
+            return Ok(Some(count));
+        }
+    }
+    Ok(None)
+}
+
+/// The commit HEAD points at, `None` on an unborn branch.
+pub fn head_id(repo: &Repository) -> Result<Option<ObjectId>, GitError> {
+    resolve(repo, "HEAD")
+}
+
+/// What source a build was made from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The commit HEAD points at.
+    pub sha: Option<String>,
+    /// The branch HEAD points at, `None` when detached.
+    pub branch: Option<String>,
+    /// Whether the work tree differs from HEAD.
+    pub dirty: bool,
+    /// The id of the tree of the work tree when dirty, identifying the exact uncommitted state.
+    pub diff_hash: Option<String>,
+}
+impl Provenance {
+    /// Used outside of a git repository.
+    pub const UNKNOWN: Self = Self {
+        sha: None,
+        branch: None,
+        dirty: false,
+        diff_hash: None,
+    };
+
+    /// The provenance of the work tree, or [`Provenance::UNKNOWN`] if it can't be determined.
+    pub fn current_or_unknown() -> Self {
+        Self::or_unknown(open_repo().and_then(|repo| provenance(&repo)))
+    }
+
+    /// Only the commit and branch of HEAD, for builds that are never run such as `cargo frc check`,
+    /// which aren't worth walking the work tree for. The work tree counts as clean.
+    pub fn head_or_unknown() -> Self {
+        Self::or_unknown(open_repo().and_then(|repo| {
+            Ok(Self {
+                sha: resolve(&repo, "HEAD")?.map(|head| head.to_string()),
+                branch: head_branch(&repo)?,
+                ..Self::UNKNOWN
+            })
+        }))
+    }
+
+    fn or_unknown(provenance: Result<Self, GitError>) -> Self {
+        provenance.unwrap_or_else(|err| {
+            tracing::warn!("Could not determine the git provenance of the build: {}", err);
+            Self::UNKNOWN
+        })
+    }
+
+    /// The `FRC_GIT_*` environment variables for robot code to read with `env!`.
+    pub fn env_vars(&self) -> [(&'static str, String); 4] {
+        const UNKNOWN: &str = "unknown";
+        [
+            ("FRC_GIT_SHA", self.sha.clone().unwrap_or(UNKNOWN.into())),
+            ("FRC_GIT_BRANCH", self.branch.clone().unwrap_or(UNKNOWN.into())),
+            ("FRC_GIT_DIRTY", self.dirty.to_string()),
+            ("FRC_GIT_DIFF_HASH", self.diff_hash.clone().unwrap_or_default()),
+        ]
+    }
+
+    pub fn apply_env(&self, cmd: &mut std::process::Command) {
+        for (key, value) in self.env_vars() {
+            cmd.env(key, value);
+        }
+    }
+}
+
+pub fn provenance(repo: &Repository) -> Result<Provenance, GitError> {
+    provenance_of(repo, snapshot_work_tree(repo, Snapshot::HashOnly)?)
+}
+
+/// The provenance of `work_tree`, a snapshot of the work tree of `repo`.
+fn provenance_of(repo: &Repository, work_tree: ObjectId) -> Result<Provenance, GitError> {
+    let head = resolve(repo, "HEAD")?;
+    let head_tree = match head {
+        Some(head) => Some(
+            repo.find_object(head)
+                .map_err(|err| GitError::Reference {
+                    name: "HEAD".to_owned(),
+                    source: err.into(),
+                })?
+                .peel_to_tree()
+                .map_err(|err| GitError::Reference {
+                    name: "HEAD^{tree}".to_owned(),
+                    source: err.into(),
+                })?
+                .id,
+        ),
+        None => None,
+    };
+    let dirty = Some(work_tree) != head_tree;
+
+    Ok(Provenance {
+        sha: head.map(|head| head.to_string()),
+        branch: head_branch(repo)?,
+        dirty,
+        diff_hash: dirty.then(|| work_tree.to_string()),
+    })
+}
+
+/// The short name of the branch HEAD points at, `None` when detached.
+fn head_branch(repo: &Repository) -> Result<Option<String>, GitError> {
+    Ok(repo
+        .head_name()
+        .map_err(|err| GitError::Reference {
+            name: "HEAD".to_owned(),
+            source: err.into(),
+        })?
+        .map(|name| name.shorten().to_string()))
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use gix::{Repository, index::entry};
+
+    /// A new repository in a temporary directory, with a committer configured.
+    fn temp_repo(name: &str) -> Repository {
+        let dir = std::env::temp_dir().join(format!("frc-git-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        gix::init(&dir).unwrap();
+        let config = dir.join(".git").join("config");
+        let mut contents = std::fs::read_to_string(&config).unwrap();
+        contents.push_str("[user]\n\tname = Robot\n\temail = robot@example.com\n");
+        std::fs::write(&config, contents).unwrap();
+        std::fs::write(dir.join(".gitignore"), "/target\n").unwrap();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("target").join("robot"), "binary").unwrap();
+        gix::open(&dir).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_work_tree() {
+        let repo = temp_repo("snapshot");
+        let tree = snapshot_work_tree(&repo, Snapshot::Write).unwrap();
+        assert_eq!(snapshot_work_tree(&repo, Snapshot::HashOnly).unwrap(), tree);
+
+        let tree = repo
+            .find_object(tree)
+            .unwrap()
+            .into_tree()
+            .decode()
+            .unwrap()
+            .to_owned();
+        let names = tree
+            .entries
+            .iter()
+            .map(|entry| entry.filename.to_string())
+            .collect::<Vec<_>>();
+        // untracked files are part of the snapshot, ignored ones aren't
+        assert_eq!(names, [".gitignore", "src"]);
+
+        let provenance = provenance(&repo).unwrap();
+        assert_eq!(provenance.sha, None);
+        assert!(provenance.dirty);
+        assert!(provenance.diff_hash.is_some());
+    }
+
+    #[test]
+    fn test_snapshot_filters() {
+        let repo = temp_repo("filters");
+        let dir = repo.work_dir().unwrap().to_path_buf();
+        let config = dir.join(".git").join("config");
+        let mut contents = std::fs::read_to_string(&config).unwrap();
+        contents.push_str("[core]\n\tautocrlf = true\n");
+        std::fs::write(&config, contents).unwrap();
+        std::fs::write(dir.join("src").join("main.rs"), "fn main() {}\r\n").unwrap();
+        let repo = gix::open(&dir).unwrap();
+
+        let tree = snapshot_work_tree(&repo, Snapshot::Write).unwrap();
+        let tree = repo.find_object(tree).unwrap().peel_to_tree().unwrap();
+        let main = tree
+            .lookup_entry_by_path("src/main.rs", &mut Vec::new())
+            .unwrap()
+            .unwrap();
+        // a checkout with CRLF line endings hashes like the committed LF content
+        assert_eq!(main.object().unwrap().data, b"fn main() {}\n");
+    }
 
     #[test]
-    fn test_make_commit() {
-        let cwd = std::env::current_dir().unwrap();
-        println!("cwd: {:?}", cwd);
-        let repo = gix::discover(cwd).unwrap();
-        // for entry in repo.
+    fn test_deploy_commit() {
+        let repo = temp_repo("commit");
+        let tree = snapshot_work_tree(&repo, Snapshot::Write).unwrap();
+        let commit = prepare_commit(repo, "flr", Some("yin"), Local::now(), tree).unwrap();
+        assert_eq!(commit.branch(), "comp/flr");
+        // the branch only exists once the deploy succeeded
+        assert_eq!(resolve(&commit.repo, "refs/heads/comp/flr").unwrap(), None);
+        commit.publish().unwrap();
+        assert_eq!(
+            resolve(&commit.repo, "refs/heads/comp/flr").unwrap(),
+            Some(commit.id)
+        );
+        // a commit prepared against an old tip doesn't overwrite a newer deploy
+        assert!(commit.publish().is_err());
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,9 @@
 pub mod deploy_descriptor;
 pub mod ssh_client;
-pub mod git_commit;
\ No newline at end of file
+pub mod git_commit;
+pub mod deploy_manifest;
+pub mod deploy_lock;
+pub mod remote_log;
+pub mod connections;
+pub mod report;
+pub mod remote_probe;
\ No newline at end of file
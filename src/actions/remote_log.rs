@@ -0,0 +1,227 @@
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+
+use super::ssh_client::{RemoteShell, SSHErrors};
+
+/// The severity of a log line, as printed by `tracing` and most loggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+impl Level {
+    fn parse(token: &str) -> Option<Self> {
+        let token = token.trim_matches(|c: char| !c.is_ascii_alphabetic());
+        match token.to_ascii_uppercase().as_str() {
+            "TRACE" => Some(Self::Trace),
+            "DEBUG" => Some(Self::Debug),
+            "INFO" => Some(Self::Info),
+            "WARN" | "WARNING" => Some(Self::Warn),
+            "ERROR" | "ERR" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// The ANSI SGR code lines of this level are printed with.
+    fn color(self) -> &'static str {
+        match self {
+            Self::Trace => "2",
+            Self::Debug => "34",
+            Self::Info => "32",
+            Self::Warn => "33",
+            Self::Error => "31",
+        }
+    }
+}
+
+/// The command that prints the log at `path`, following it if `follow` is set.
+///
+/// Without `lines` the whole log is printed, which `--since` needs to find its starting point.
+pub fn tail_command(path: &str, follow: bool, lines: Option<usize>) -> String {
+    let lines = match lines {
+        Some(lines) => lines.to_string(),
+        None => "+1".to_owned(),
+    };
+    let follow = if follow { " -F" } else { "" };
+    format!("tail -n {lines}{follow} {}", quote_path(path))
+}
+
+/// Quotes a path for the remote shell, leaving a leading `~/` to be expanded.
+pub fn quote_path(path: &str) -> String {
+    let (home, rest) = match path.strip_prefix("~/") {
+        Some(rest) => ("~/", rest),
+        None => ("", path),
+    };
+    format!("{home}'{}'", rest.replace('\'', r"'\''"))
+}
+
+/// Decides which lines of a log are shown.
+///
+/// Multi-line entries such as backtraces only carry a level and timestamp on their first line,
+/// so lines without either are shown if the line that started their entry was.
+pub struct LogFilter {
+    pub level: Option<Level>,
+    pub pattern: Option<Regex>,
+    pub since: Option<DateTime<FixedOffset>>,
+    entry_level: Option<Level>,
+    entry_shown: bool,
+}
+impl LogFilter {
+    pub fn new(
+        level: Option<Level>,
+        pattern: Option<Regex>,
+        since: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        Self {
+            level,
+            pattern,
+            since,
+            entry_level: None,
+            entry_shown: true,
+        }
+    }
+
+    /// Whether `line` is shown, and the level of the entry it belongs to.
+    pub fn check(&mut self, line: &str) -> (bool, Option<Level>) {
+        let mut tokens = line.split_whitespace().take(4).peekable();
+        let time = tokens
+            .peek()
+            .and_then(|token| DateTime::parse_from_rfc3339(token.trim_matches(['[', ']'])).ok());
+        let level = tokens.find_map(Level::parse);
+
+        if time.is_some() || level.is_some() {
+            self.entry_level = level;
+            self.entry_shown = match (self.since, time) {
+                (Some(since), Some(time)) => time >= since,
+                // entries without a timestamp after a timestamped one are just as recent
+                (Some(_), None) => self.entry_shown,
+                (None, _) => true,
+            } && match (self.level, level) {
+                (Some(min), Some(level)) => level >= min,
+                _ => true,
+            };
+        }
+
+        let matches = match &self.pattern {
+            Some(pattern) => pattern.is_match(line),
+            None => true,
+        };
+        (self.entry_shown && matches, self.entry_level)
+    }
+}
+
+/// Colors a line by the level of its entry.
+pub fn colorize(line: &str, level: Option<Level>) -> String {
+    match level {
+        Some(level) => format!("\x1b[{}m{}\x1b[0m", level.color(), line),
+        None => line.to_owned(),
+    }
+}
+
+/// Runs `command` and hands every complete line of its output to `line`.
+pub async fn stream_lines(
+    shell: &mut dyn RemoteShell,
+    command: &str,
+    mut line: impl FnMut(&str) + Send,
+) -> Result<u32, SSHErrors> {
+    let mut pending = Vec::new();
+    let code = shell
+        .exec(command, &mut |data: &[u8]| {
+            pending.extend_from_slice(data);
+            while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                let rest = pending.split_off(end + 1);
+                line(String::from_utf8_lossy(&pending).trim_end_matches(['\r', '\n']));
+                pending = rest;
+            }
+        })
+        .await?;
+    if !pending.is_empty() {
+        line(&String::from_utf8_lossy(&pending));
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use tokio::io::AsyncReadExt;
+
+    use crate::actions::ssh_client::Output;
+
+    use super::*;
+
+    /// Runs commands with the local `sh` in place of the target.
+    pub(crate) struct LocalShell;
+
+    #[async_trait::async_trait]
+    impl RemoteShell for LocalShell {
+        async fn exec(&mut self, command: &str, output: &mut Output<'_>) -> Result<u32, SSHErrors> {
+            let mut child = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .stdout(std::process::Stdio::piped())
+                .spawn()?;
+            let mut stdout = child.stdout.take().expect("stdout is piped");
+            let mut buf = [0; 7];
+            loop {
+                let read = stdout.read(&mut buf).await?;
+                if read == 0 {
+                    break;
+                }
+                output(&buf[..read]);
+            }
+            Ok(child.wait().await?.code().unwrap_or(1) as u32)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_filtered_log() {
+        let path = std::env::temp_dir().join(format!("frc-log-{}.log", std::process::id()));
+        std::fs::write(
+            &path,
+            "2024-03-01T12:00:00Z  INFO robot: enabled\n\
+             2024-03-01T12:00:01Z ERROR robot: arm stalled\n\
+             \tat arm.rs:12\n\
+             2024-03-01T12:00:02Z DEBUG robot: arm at 40 degrees\n\
+             2024-03-01T12:00:03Z  WARN robot: brownout\n",
+        )
+        .unwrap();
+
+        let mut filter = LogFilter::new(
+            Some(Level::Warn),
+            None,
+            Some(DateTime::parse_from_rfc3339("2024-03-01T12:00:01Z").unwrap()),
+        );
+        let mut shown = Vec::new();
+        let command = tail_command(path.to_str().unwrap(), false, None);
+        let code = stream_lines(&mut LocalShell, &command, |line| {
+            if filter.check(line).0 {
+                shown.push(line.to_owned());
+            }
+        })
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(code, 0);
+        assert_eq!(
+            shown,
+            vec![
+                "2024-03-01T12:00:01Z ERROR robot: arm stalled",
+                "\tat arm.rs:12",
+                "2024-03-01T12:00:03Z  WARN robot: brownout",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_filter_pattern() {
+        let mut filter = LogFilter::new(None, Some(Regex::new("arm").unwrap()), None);
+        assert_eq!(filter.check("INFO arm raised"), (true, Some(Level::Info)));
+        assert_eq!(filter.check("ERROR drive stalled"), (false, Some(Level::Error)));
+        assert_eq!(filter.check("  no level here"), (false, Some(Level::Error)));
+    }
+}
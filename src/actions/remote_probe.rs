@@ -0,0 +1,203 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use chrono::Utc;
+use thiserror::Error;
+use tokio::{net::TcpStream, time::Instant};
+
+use super::{
+    deploy_descriptor::{EndpointError, HealthCheck, HealthSignal, Probe},
+    remote_log::quote_path,
+    ssh_client::{RemoteShell, SSHErrors},
+};
+
+#[derive(Debug, Error)]
+pub enum ProbeError {
+    #[error("{0}")]
+    Remote(#[from] SSHErrors),
+    #[error("{0}")]
+    Endpoint(#[from] EndpointError),
+    #[error("`{command}` exited with {code}")]
+    Exit { command: String, code: u32 },
+    #[error("`{command}` printed {output:?}")]
+    Unexpected { command: String, output: String },
+}
+
+/// Runs `command` on the target and returns its output, failing if it exits with an error.
+async fn output_of(shell: &mut dyn RemoteShell, command: &str) -> Result<String, ProbeError> {
+    let mut output = Vec::new();
+    let code = shell
+        .exec(command, &mut |data: &[u8]| output.extend_from_slice(data))
+        .await?;
+    if code != 0 {
+        return Err(ProbeError::Exit {
+            command: command.to_owned(),
+            code,
+        });
+    }
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// What the probe reads from the target, the output of its command or the body of its response.
+pub async fn read(
+    probe: &Probe,
+    shell: &mut dyn RemoteShell,
+    client: &reqwest::Client,
+    addr: Ipv4Addr,
+) -> Result<String, ProbeError> {
+    match probe {
+        Probe::Command(command) => output_of(shell, command).await,
+        Probe::Http(http) => Ok(http.call(client, addr).await?),
+    }
+}
+
+/// How many seconds the clock of the target is ahead of this computer's, negative if behind.
+pub async fn clock_skew(shell: &mut dyn RemoteShell) -> Result<i64, ProbeError> {
+    const COMMAND: &str = "date +%s";
+    let output = output_of(shell, COMMAND).await?;
+    let remote = output
+        .trim()
+        .parse::<i64>()
+        .map_err(|_| ProbeError::Unexpected {
+            command: COMMAND.to_owned(),
+            output: output.clone(),
+        })?;
+    Ok(remote - Utc::now().timestamp())
+}
+
+/// The size of the file at `path` on the target, 0 if it doesn't exist.
+pub async fn file_len(shell: &mut dyn RemoteShell, path: &str) -> Result<u64, ProbeError> {
+    let command = format!("wc -c < {}", quote_path(path));
+    match output_of(shell, &command).await {
+        Ok(output) => output
+            .trim()
+            .parse()
+            .map_err(|_| ProbeError::Unexpected { command, output }),
+        Err(ProbeError::Exit { .. }) => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+/// Where the robot-code is looked for by [`wait_healthy`].
+pub struct HealthTarget<'a> {
+    pub addr: Ipv4Addr,
+    /// The file name of the deployed binary, for `$BINARY$`.
+    pub binary: &'a str,
+    pub log_path: &'a str,
+    /// The size of the log before the robot-code was started, only later lines are matched.
+    pub log_offset: u64,
+}
+
+/// Waits for the signal of `check` after the robot-code was started, returns why it's unhealthy.
+///
+/// A log line or readiness probe has to succeed once within the timeout, while the process
+/// has to be running at the end of it without having exited in between.
+pub async fn wait_healthy(
+    check: &HealthCheck,
+    shell: &mut dyn RemoteShell,
+    client: &reqwest::Client,
+    target: &HealthTarget<'_>,
+) -> Result<Option<String>, ProbeError> {
+    let deadline = Instant::now() + check.timeout;
+    let mut log_offset = target.log_offset;
+    let mut seen_alive = false;
+    let mut last_error = None;
+    loop {
+        match &check.signal {
+            HealthSignal::Process(command) => {
+                let command = command.replace("$BINARY$", &quote_path(target.binary));
+                let alive = shell.exec(&command, &mut |_: &[u8]| {}).await? == 0;
+                if alive {
+                    seen_alive = true;
+                } else if seen_alive {
+                    return Ok(Some(format!("the robot-code exited, `{command}` failed")));
+                }
+                if Instant::now() >= deadline {
+                    return Ok((!alive).then(|| {
+                        format!(
+                            "the robot-code isn't running after {}s, `{command}` failed",
+                            check.timeout.as_secs()
+                        )
+                    }));
+                }
+            }
+            HealthSignal::LogLine(pattern) => {
+                // a log that shrank was started over by the new robot-code
+                if file_len(shell, target.log_path).await? < log_offset {
+                    log_offset = 0;
+                }
+                let command = format!(
+                    "tail -c +{} {}",
+                    log_offset + 1,
+                    quote_path(target.log_path)
+                );
+                if output_of(shell, &command)
+                    .await?
+                    .lines()
+                    .any(|line| pattern.is_match(line))
+                {
+                    return Ok(None);
+                }
+                last_error = Some(format!(
+                    "no line of {} matched `{pattern}`",
+                    target.log_path
+                ));
+            }
+            HealthSignal::Http(http) => match http.call(client, target.addr).await {
+                Ok(_) => return Ok(None),
+                Err(err) => last_error = Some(err.to_string()),
+            },
+            HealthSignal::Tcp(port) => {
+                let connect = TcpStream::connect((target.addr, *port));
+                match tokio::time::timeout(check.interval.max(Duration::from_secs(1)), connect)
+                    .await
+                {
+                    Ok(Ok(_)) => return Ok(None),
+                    Ok(Err(err)) => last_error = Some(format!("port {port}: {err}")),
+                    Err(_) => last_error = Some(format!("port {port} didn't accept a connection")),
+                }
+            }
+        }
+        if Instant::now() >= deadline {
+            return Ok(Some(format!(
+                "the robot-code wasn't ready after {}s, {}",
+                check.timeout.as_secs(),
+                last_error.unwrap_or_default()
+            )));
+        }
+        tokio::time::sleep(check.interval).await;
+    }
+}
+
+/// The bytes available on the filesystem holding `path` on the target,
+/// `None` if `df` failed or printed something unexpected.
+pub async fn free_space(shell: &mut dyn RemoteShell, path: &str) -> Result<Option<u64>, SSHErrors> {
+    let mut output = Vec::new();
+    let command = format!("df -Pk '{}'", path.replace('\'', r"'\''"));
+    let code = shell
+        .exec(&command, &mut |data: &[u8]| output.extend_from_slice(data))
+        .await?;
+    if code != 0 {
+        return Ok(None);
+    }
+    Ok(parse_df(&String::from_utf8_lossy(&output)))
+}
+
+/// Reads the available space from the POSIX output of `df -Pk` for a single path.
+fn parse_df(output: &str) -> Option<u64> {
+    // `-P` keeps every filesystem on one line: device, size, used, available, capacity, mount
+    let available = output.lines().nth(1)?.split_whitespace().nth(3)?;
+    available.parse::<u64>().ok().map(|kib| kib * 1024)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_df() {
+        let output = "Filesystem     1024-blocks   Used Available Capacity Mounted on\n\
+                      ubi0:rootfs         442092 223284    218808      51% /\n";
+        assert_eq!(parse_df(output), Some(218808 * 1024));
+        assert_eq!(parse_df("df: /nope: No such file or directory\n"), None);
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::diagnostic::{Diagnostic as CargoDiagnostic, DiagnosticLevel};
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// The file format of a `--report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+/// Reads the `<FORMAT> <PATH>` values of a `--report` option.
+pub fn parse_option(values: &[String]) -> Result<(ReportFormat, PathBuf), String> {
+    let [format, path] = values else {
+        return Err("`--report` takes a format and a path".to_owned());
+    };
+    let format = ReportFormat::from_str(format, true)
+        .map_err(|_| format!("{format:?} is not a report format, use junit or json"))?;
+    Ok((format, PathBuf::from(path)))
+}
+
+/// The outcome of a `cargo frc check` or `cargo frc test`, for publishing from a build box.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// `check` or `test`.
+    pub command: String,
+    /// `real` or `sim`.
+    pub runtime: String,
+    pub dev: bool,
+    /// The `--cfg` flags the code was built with.
+    pub cfgs: Vec<String>,
+    pub success: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    pub suites: Vec<Suite>,
+}
+
+/// A compiler warning or error.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub level: String,
+    pub message: String,
+    /// The lint or error code, e.g. `E0308` or `clippy::needless_return`.
+    pub code: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// The diagnostic as rustc prints it.
+    pub rendered: Option<String>,
+}
+
+/// The tests of one test binary.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suite {
+    pub name: String,
+    /// The wall time of the whole binary in seconds, as reported by libtest.
+    pub time: Option<f64>,
+    pub tests: Vec<TestCase>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestCase {
+    pub name: String,
+    pub outcome: Outcome,
+    /// Only reported by libtest with `-Z unstable-options --report-time`.
+    pub time: Option<f64>,
+    /// What a failed test printed.
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+impl Report {
+    pub fn new(command: &str, cfgs: &[&str], runtime: &str, dev: bool) -> Self {
+        Self {
+            command: command.to_owned(),
+            runtime: runtime.to_owned(),
+            dev,
+            cfgs: cfgs.iter().map(|cfg| cfg.to_string()).collect(),
+            success: false,
+            diagnostics: Vec::new(),
+            suites: Vec::new(),
+        }
+    }
+
+    /// Records a diagnostic at its primary span, notes and help without a location are skipped.
+    pub fn add_diagnostic(&mut self, diagnostic: &CargoDiagnostic) {
+        let level = match diagnostic.level {
+            DiagnosticLevel::Ice => "ice",
+            DiagnosticLevel::Error => "error",
+            DiagnosticLevel::Warning => "warning",
+            DiagnosticLevel::FailureNote => "failure-note",
+            DiagnosticLevel::Note => "note",
+            DiagnosticLevel::Help => "help",
+            _ => "unknown",
+        };
+        let span = diagnostic.spans.iter().find(|span| span.is_primary);
+        if span.is_none() && matches!(level, "note" | "help" | "failure-note") {
+            return;
+        }
+        self.diagnostics.push(Diagnostic {
+            level: level.to_owned(),
+            message: diagnostic.message.clone(),
+            code: diagnostic.code.as_ref().map(|code| code.code.clone()),
+            file: span.map(|span| span.file_name.clone()),
+            line: span.map(|span| span.line_start),
+            column: span.map(|span| span.column_start),
+            rendered: diagnostic.rendered.clone(),
+        });
+    }
+
+    pub fn write(&self, path: &Path, format: ReportFormat) -> std::io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let data = match format {
+            ReportFormat::Json => serde_json::to_string_pretty(self)?,
+            ReportFormat::Junit => self.to_junit(),
+        };
+        std::fs::write(path, data)
+    }
+
+    /// Compiler errors become failures of a `compile` suite, so CI tools show them next to tests.
+    fn to_junit(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let name = format!("cargo-frc {} ({})", self.command, self.cfgs.join(" "));
+        let _ = writeln!(out, "<testsuites name=\"{}\">", escape(&name));
+
+        let _ = writeln!(
+            out,
+            "  <testsuite name=\"compile\" tests=\"{}\" failures=\"{}\">",
+            self.diagnostics.len(),
+            self.count_errors()
+        );
+        out.push_str("    <properties>\n");
+        let _ = writeln!(
+            out,
+            "      <property name=\"runtime\" value=\"{}\"/>",
+            escape(&self.runtime)
+        );
+        let _ = writeln!(out, "      <property name=\"dev\" value=\"{}\"/>", self.dev);
+        for cfg in &self.cfgs {
+            let _ = writeln!(
+                out,
+                "      <property name=\"cfg\" value=\"{}\"/>",
+                escape(cfg)
+            );
+        }
+        out.push_str("    </properties>\n");
+        for diagnostic in &self.diagnostics {
+            let location = match (&diagnostic.file, diagnostic.line) {
+                (Some(file), Some(line)) => format!("{file}:{line}"),
+                (Some(file), None) => file.clone(),
+                _ => "unknown".to_owned(),
+            };
+            let _ = write!(
+                out,
+                "    <testcase classname=\"compile\" name=\"{}\" file=\"{}\"",
+                escape(&format!("{} {}", location, diagnostic.message)),
+                escape(diagnostic.file.as_deref().unwrap_or_default()),
+            );
+            if let Some(line) = diagnostic.line {
+                let _ = write!(out, " line=\"{line}\"");
+            }
+            let body = escape(
+                diagnostic
+                    .rendered
+                    .as_deref()
+                    .unwrap_or(&diagnostic.message),
+            );
+            if matches!(diagnostic.level.as_str(), "error" | "ice") {
+                let _ = writeln!(
+                    out,
+                    ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                    escape(&diagnostic.message),
+                    body
+                );
+            } else {
+                let _ = writeln!(
+                    out,
+                    ">\n      <system-out>{}</system-out>\n    </testcase>",
+                    body
+                );
+            }
+        }
+        out.push_str("  </testsuite>\n");
+
+        for suite in &self.suites {
+            let failures = suite.count(Outcome::Failed);
+            let _ = write!(
+                out,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\"",
+                escape(&suite.name),
+                suite.tests.len(),
+                failures,
+                suite.count(Outcome::Ignored)
+            );
+            if let Some(time) = suite.time {
+                let _ = write!(out, " time=\"{time}\"");
+            }
+            out.push_str(">\n");
+            for test in &suite.tests {
+                let _ = write!(
+                    out,
+                    "    <testcase classname=\"{}\" name=\"{}\"",
+                    escape(&suite.name),
+                    escape(&test.name)
+                );
+                if let Some(time) = test.time {
+                    let _ = write!(out, " time=\"{time}\"");
+                }
+                match test.outcome {
+                    Outcome::Passed => out.push_str("/>\n"),
+                    Outcome::Ignored => out.push_str(">\n      <skipped/>\n    </testcase>\n"),
+                    Outcome::Failed => {
+                        let _ = writeln!(
+                            out,
+                            ">\n      <failure message=\"failed\">{}</failure>\n    </testcase>",
+                            escape(test.output.as_deref().unwrap_or_default())
+                        );
+                    }
+                }
+            }
+            out.push_str("  </testsuite>\n");
+        }
+        out.push_str("</testsuites>\n");
+        out
+    }
+
+    fn count_errors(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| matches!(diagnostic.level.as_str(), "error" | "ice"))
+            .count()
+    }
+}
+
+impl Suite {
+    fn count(&self, outcome: Outcome) -> usize {
+        self.tests
+            .iter()
+            .filter(|test| test.outcome == outcome)
+            .count()
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // ansi escapes from colored output are not valid xml
+            c if c.is_control() && !matches!(c, '\n' | '\t' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reads the human output of a libtest binary line by line.
+///
+/// Only the stable output format is understood, per test times show up
+/// when the binary is run with `-Z unstable-options --report-time`.
+pub struct LibtestParser {
+    suite: Suite,
+    /// The failed test whose captured output is being read, and that output.
+    capturing: Option<(String, String)>,
+}
+impl LibtestParser {
+    pub fn new(suite: &str) -> Self {
+        Self {
+            suite: Suite {
+                name: suite.to_owned(),
+                time: None,
+                tests: Vec::new(),
+            },
+            capturing: None,
+        }
+    }
+
+    pub fn line(&mut self, line: &str) {
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            self.end_capture();
+            self.capturing = Some((name.to_owned(), String::new()));
+            return;
+        }
+        if line == "failures:" || line.starts_with("test result: ") {
+            self.end_capture();
+        }
+        if let Some((_, output)) = &mut self.capturing {
+            output.push_str(line);
+            output.push('\n');
+            return;
+        }
+
+        if let Some(result) = line.strip_prefix("test result: ") {
+            self.suite.time = result
+                .rsplit_once("finished in ")
+                .and_then(|(_, time)| parse_seconds(time));
+        } else if let Some((name, result)) = line
+            .strip_prefix("test ")
+            .and_then(|rest| rest.split_once(" ... "))
+        {
+            let (result, time) = match result.split_once(" <") {
+                Some((result, time)) => (result, parse_seconds(time.trim_end_matches('>'))),
+                None => (result, None),
+            };
+            let outcome = match result.split(',').next().unwrap_or_default().trim() {
+                "ok" => Outcome::Passed,
+                "FAILED" => Outcome::Failed,
+                "ignored" => Outcome::Ignored,
+                _ => return,
+            };
+            self.suite.tests.push(TestCase {
+                name: name.to_owned(),
+                outcome,
+                time,
+                output: None,
+            });
+        }
+    }
+
+    fn end_capture(&mut self) {
+        let Some((name, output)) = self.capturing.take() else {
+            return;
+        };
+        if let Some(test) = self.suite.tests.iter_mut().find(|test| test.name == name) {
+            test.output = Some(output.trim_matches(['\n', ' ']).to_owned());
+        }
+    }
+
+    pub fn finish(mut self) -> Suite {
+        self.end_capture();
+        self.suite
+    }
+}
+
+fn parse_seconds(time: &str) -> Option<f64> {
+    time.trim().strip_suffix('s')?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_libtest_output() {
+        let mut parser = LibtestParser::new("robot");
+        for line in "\n\
+            running 3 tests\n\
+            test arm::tests::limits ... ok\n\
+            test drive::tests::slow ... ignored, needs the field\n\
+            test arm::tests::stall ... FAILED <0.012s>\n\
+            \n\
+            failures:\n\
+            \n\
+            ---- arm::tests::stall stdout ----\n\
+            thread 'arm::tests::stall' panicked at src/arm.rs:40:9:\n\
+            stalled\n\
+            \n\
+            \n\
+            failures:\n\
+            \x20   arm::tests::stall\n\
+            \n\
+            test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.02s\n"
+            .lines()
+        {
+            parser.line(line);
+        }
+        let suite = parser.finish();
+
+        assert_eq!(suite.time, Some(0.02));
+        let outcomes = suite
+            .tests
+            .iter()
+            .map(|test| (test.name.as_str(), test.outcome, test.time))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("arm::tests::limits", Outcome::Passed, None),
+                ("drive::tests::slow", Outcome::Ignored, None),
+                ("arm::tests::stall", Outcome::Failed, Some(0.012)),
+            ]
+        );
+        assert_eq!(
+            suite.tests[2].output.as_deref(),
+            Some("thread 'arm::tests::stall' panicked at src/arm.rs:40:9:\nstalled")
+        );
+    }
+}
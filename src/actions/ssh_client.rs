@@ -1,11 +1,9 @@
-use std::{sync::Arc, net::{Ipv4Addr, SocketAddrV4}, collections::HashMap, io};
+use std::{sync::Arc, net::{Ipv4Addr, SocketAddrV4}, collections::HashMap, io, fmt, time::{Duration, Instant, UNIX_EPOCH}};
 
 use tracing::{error, info};
 use russh::{*, client::Handle};
-use russh_sftp::{client::SftpSession, protocol::FileType};
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
-
-use crate::configs::project_config::ProjectConfig;
+use russh_sftp::{client::SftpSession, protocol::{FileAttributes, FileType, OpenFlags}};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
 
 use super::deploy_descriptor::Descriptor;
 
@@ -22,6 +20,232 @@ pub enum SSHErrors {
     SFTPError(#[from] russh_sftp::client::error::Error),
     #[error("{0}")]
     IOError(#[from] io::Error),
+    /// Reading or writing the file on this side of a transfer failed.
+    #[error("local file: {0}")]
+    LocalIOError(#[source] io::Error),
+}
+
+impl SSHErrors {
+    /// Whether the error is likely to go away by connecting again, like a dropped radio link.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::IOError(_)
+                | Self::SSHError(
+                    russh::Error::IO(_)
+                        | russh::Error::Disconnect
+                        | russh::Error::ConnectionTimeout
+                        | russh::Error::KeepaliveTimeout
+                )
+        )
+    }
+}
+
+/// How often an idle session is checked to still be alive.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(2);
+/// How many keepalives may go unanswered before the session counts as lost.
+const KEEPALIVE_MAX: usize = 3;
+
+/// Receives the output of a command as it arrives.
+pub type Output<'a> = dyn FnMut(&[u8]) + Send + 'a;
+
+/// Runs commands on the target, implemented by [`SSHConnection`] and by local stand-ins in tests.
+#[async_trait::async_trait]
+pub trait RemoteShell: Send {
+    /// Runs `command`, handing its stdout to `output` as it arrives, and returns its exit code.
+    async fn exec(&mut self, command: &str, output: &mut Output<'_>) -> Result<u32, SSHErrors>;
+}
+
+/// How far along a transfer is, handed to the progress callback after every chunk.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Bytes of the file on the receiving side, including the offset a transfer resumed from.
+    pub transferred: u64,
+    pub total: Option<u64>,
+    /// Bytes per second moved by this transfer.
+    pub throughput: f64,
+}
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", human_bytes(self.transferred as f64))?;
+        if let Some(total) = self.total {
+            write!(f, " / {}", human_bytes(total as f64))?;
+            if let Some(percent) = (self.transferred * 100).checked_div(total) {
+                write!(f, " ({}%)", percent)?;
+            }
+        }
+        write!(f, " at {}/s", human_bytes(self.throughput))
+    }
+}
+
+/// Formats a byte count with a binary unit, like `1.5 MiB`.
+pub fn human_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Receives the progress of a transfer.
+pub type ProgressFn<'a> = dyn FnMut(Progress) + Send + 'a;
+
+/// A progress callback that redraws a line on stderr, if stderr is a terminal.
+pub fn progress_printer(label: String) -> impl FnMut(Progress) + Send {
+    use std::io::{IsTerminal, Write};
+
+    let terminal = std::io::stderr().is_terminal();
+    let mut last = None::<Instant>;
+    move |progress| {
+        let done = progress.total == Some(progress.transferred);
+        let due = match last {
+            Some(last) => last.elapsed() > Duration::from_millis(100),
+            None => true,
+        };
+        if !terminal || !(done || due) {
+            return;
+        }
+        last = Some(Instant::now());
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[2K{}: {}", label, progress);
+        if done {
+            let _ = writeln!(stderr);
+        }
+    }
+}
+
+/// Permissions and modification time carried to the other side of a transfer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileMeta {
+    /// The unix permission bits, like `0o755`.
+    pub mode: Option<u32>,
+    /// Seconds since the unix epoch.
+    pub mtime: Option<u32>,
+}
+impl FileMeta {
+    pub fn of_local(path: &std::path::Path) -> io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_secs() as u32);
+        Ok(Self { mode, mtime })
+    }
+
+    pub fn apply_local(&self, path: &std::path::Path) -> io::Result<()> {
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+        }
+        if let Some(mtime) = self.mtime {
+            std::fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(UNIX_EPOCH + Duration::from_secs(mtime.into()))?;
+        }
+        Ok(())
+    }
+}
+
+/// The owner of a file on the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// A file found by [`SSHConnection::walk_dir`].
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    /// The path of the file, starting with the walked directory.
+    pub path: String,
+    pub file_type: FileType,
+    pub size: u64,
+    /// Seconds since the unix epoch.
+    pub mtime: Option<u32>,
+}
+
+/// How a file is moved by [`SSHConnection::upload_from`] and [`SSHConnection::download_to`].
+#[derive(Debug, Clone, Copy)]
+pub struct TransferOptions {
+    /// How many bytes are read and written at a time.
+    pub chunk_size: usize,
+    /// Where in the file to start, to resume an interrupted upload or download.
+    pub offset: u64,
+    /// Set on the uploaded file once it is complete.
+    pub preserve: Option<FileMeta>,
+}
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 64 * 1024,
+            offset: 0,
+            preserve: None,
+        }
+    }
+}
+
+/// A failure of [`copy_chunked`], telling the side it happened on.
+#[derive(Debug)]
+enum CopyError {
+    Read(io::Error),
+    Write(io::Error),
+}
+
+async fn copy_chunked(
+    reader: &mut (impl AsyncRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+    options: &TransferOptions,
+    total: Option<u64>,
+    progress: &mut ProgressFn<'_>,
+) -> Result<u64, CopyError> {
+    let start = Instant::now();
+    let mut buffer = vec![0; options.chunk_size.max(1)];
+    let mut copied = 0;
+    loop {
+        let read = reader.read(&mut buffer).await.map_err(CopyError::Read)?;
+        if read == 0 {
+            break;
+        }
+        writer
+            .write_all(&buffer[..read])
+            .await
+            .map_err(CopyError::Write)?;
+        copied += read as u64;
+        let elapsed = start.elapsed().as_secs_f64();
+        progress(Progress {
+            transferred: options.offset + copied,
+            total,
+            throughput: if elapsed > 0.0 { copied as f64 / elapsed } else { 0.0 },
+        });
+    }
+    Ok(copied)
+}
+
+/// Seeks both `reader` and `writer` to `options.offset` and copies the rest with [`copy_chunked`].
+async fn copy_from_offset(
+    reader: &mut (impl AsyncRead + AsyncSeek + Unpin),
+    writer: &mut (impl AsyncWrite + AsyncSeek + Unpin),
+    options: &TransferOptions,
+    total: Option<u64>,
+    progress: &mut ProgressFn<'_>,
+) -> Result<u64, CopyError> {
+    let offset = io::SeekFrom::Start(options.offset);
+    reader.seek(offset).await.map_err(CopyError::Read)?;
+    writer.seek(offset).await.map_err(CopyError::Write)?;
+    copy_chunked(reader, writer, options, total, progress).await
 }
 
 struct SSHClientImpl;
@@ -37,12 +261,25 @@ pub struct SSHConnection {
 }
 
 impl SSHConnection {
+    /// The address the connection was opened to.
+    pub fn addr(&self) -> SocketAddrV4 {
+        self.addr
+    }
+
+    /// Whether the session was closed, by either side or by missed keepalives.
+    pub fn is_closed(&self) -> bool {
+        self.ssh.is_closed()
+    }
+
+    /// Runs `command` on the target and returns its exit code.
+    ///
+    /// The output is progress for humans, it goes to stderr to keep stdout free for `--json`.
     pub async fn call(&mut self, command: &str) -> Result<u32, SSHErrors> {
         let mut channel = self.ssh.channel_open_session().await?;
         channel.exec(true, command).await?;
 
         let mut code = 0;
-        let mut stdout = tokio::io::stdout();
+        let mut stderr = tokio::io::stderr();
 
         loop {
             // There's an event available on the session channel
@@ -52,8 +289,8 @@ impl SSHConnection {
             match msg {
                 // Write data to the terminal
                 ChannelMsg::Data { ref data } => {
-                    stdout.write_all(data).await?;
-                    stdout.flush().await?;
+                    stderr.write_all(data).await?;
+                    stderr.flush().await?;
                 }
                 // The command has returned an exit code
                 ChannelMsg::ExitStatus { exit_status } => {
@@ -67,20 +304,190 @@ impl SSHConnection {
         Ok(code)
     }
 
+    /// Like [`SSHConnection::call`] but hands the output to `output` instead of printing it.
+    pub async fn call_with(&mut self, command: &str, output: &mut Output<'_>) -> Result<u32, SSHErrors> {
+        let mut channel = self.ssh.channel_open_session().await?;
+        channel.exec(true, command).await?;
+
+        let mut code = 0;
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                ChannelMsg::Data { ref data } => output(data),
+                ChannelMsg::ExitStatus { exit_status } => {
+                    code = exit_status;
+                    channel.eof().await?;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        Ok(code)
+    }
+
     pub async fn upload_file(&mut self, path: impl PathType, data: &[u8]) -> Result<(), SSHErrors> {
-        let mut file = self.sftp.create(path.as_ref().to_string()).await?;
+        self.upload_from(path, io::Cursor::new(data), &TransferOptions::default(), &mut |_| {})
+            .await?;
+        Ok(())
+    }
+
+    /// Creates `path` with `data` unless it already exists, returns whether it was created.
+    ///
+    /// The file is opened with `CREATE | EXCLUDE`, so of several callers only one creates it.
+    pub async fn create_new(&mut self, path: impl PathType, data: &[u8]) -> Result<bool, SSHErrors> {
+        let path = path.as_ref().to_string();
+        let flags = OpenFlags::CREATE | OpenFlags::EXCLUDE | OpenFlags::WRITE;
+        let mut file = match self.sftp.open_with_flags(path.clone(), flags).await {
+            Ok(file) => file,
+            // sftp v3 has no status for an existing file, servers only report a failure
+            Err(_) if self.sftp.try_exists(path.clone()).await? => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
         file.write_all(data).await?;
         file.shutdown().await?;
-        Ok(())
+        Ok(true)
     }
 
     pub async fn download_file(&mut self, path: impl PathType) -> Result<Vec<u8>, SSHErrors> {
-        let mut file = self.sftp.open(path.as_ref().to_string()).await?;
         let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).await?;
+        self.download_to(path, &mut buffer, &TransferOptions::default(), &mut |_| {})
+            .await?;
         Ok(buffer)
     }
 
+    /// Streams `reader` to `path` in chunks from `offset` and returns how many bytes were written.
+    ///
+    /// With an `offset` the remote file is kept up to it, to resume an upload that was cut off,
+    /// otherwise it's written from the start.
+    pub async fn upload_from(
+        &mut self,
+        path: impl PathType,
+        mut reader: impl AsyncRead + AsyncSeek + Unpin + Send,
+        options: &TransferOptions,
+        progress: &mut ProgressFn<'_>,
+    ) -> Result<u64, SSHErrors> {
+        let path = path.as_ref().to_string();
+        let total = reader
+            .seek(io::SeekFrom::End(0))
+            .await
+            .map_err(SSHErrors::LocalIOError)?;
+
+        let mut file = if options.offset > 0 {
+            // without TRUNCATE what was written before the offset stays
+            self.sftp.open_with_flags(path.clone(), OpenFlags::WRITE).await?
+        } else {
+            self.sftp.create(path.clone()).await?
+        };
+        let written = copy_from_offset(&mut reader, &mut file, options, Some(total), progress)
+            .await
+            .map_err(|err| match err {
+                CopyError::Read(err) => SSHErrors::LocalIOError(err),
+                CopyError::Write(err) => SSHErrors::IOError(err),
+            })?;
+        file.shutdown().await?;
+
+        if let Some(meta) = options.preserve {
+            self.set_file_meta(path.as_str(), meta).await?;
+        }
+        Ok(written)
+    }
+
+    /// Streams `path` into `writer` in chunks, starting at `offset`,
+    /// and returns the permissions and modification time of the remote file.
+    pub async fn download_to(
+        &mut self,
+        path: impl PathType,
+        mut writer: impl AsyncWrite + Unpin + Send,
+        options: &TransferOptions,
+        progress: &mut ProgressFn<'_>,
+    ) -> Result<FileMeta, SSHErrors> {
+        let path = path.as_ref().to_string();
+        let metadata = self.sftp.metadata(path.clone()).await?;
+        let mut file = self.sftp.open(path).await?;
+        file.seek(io::SeekFrom::Start(options.offset)).await?;
+        copy_chunked(&mut file, &mut writer, options, Some(metadata.len()), progress)
+            .await
+            .map_err(|err| match err {
+                CopyError::Read(err) => SSHErrors::IOError(err),
+                CopyError::Write(err) => SSHErrors::LocalIOError(err),
+            })?;
+        writer.flush().await.map_err(SSHErrors::LocalIOError)?;
+        Ok(FileMeta {
+            mode: metadata.permissions,
+            mtime: metadata.mtime,
+        })
+    }
+
+    pub async fn exists(&mut self, path: impl PathType) -> Result<bool, SSHErrors> {
+        Ok(self.sftp.try_exists(path.as_ref().to_string()).await?)
+    }
+
+    pub async fn set_file_meta(&mut self, path: impl PathType, meta: FileMeta) -> Result<(), SSHErrors> {
+        let attributes = FileAttributes {
+            permissions: meta.mode,
+            // sftp only sets both times at once
+            atime: meta.mtime,
+            mtime: meta.mtime,
+            ..FileAttributes::empty()
+        };
+        self.sftp.set_metadata(path.as_ref().to_string(), attributes).await?;
+        Ok(())
+    }
+
+    /// The uid and primary gid of `user` on the target, `None` if there is no such user.
+    pub async fn lookup_user(&mut self, user: &str) -> Result<Option<Owner>, SSHErrors> {
+        let mut output = Vec::new();
+        let code = self
+            .call_with(
+                &format!("id -u '{0}' && id -g '{0}'", user.replace('\'', r"'\''")),
+                &mut |data: &[u8]| output.extend_from_slice(data),
+            )
+            .await?;
+        if code != 0 {
+            return Ok(None);
+        }
+        let output = String::from_utf8_lossy(&output);
+        let mut ids = output.split_whitespace().map(|id| id.parse::<u32>().ok());
+        match (ids.next().flatten(), ids.next().flatten()) {
+            (Some(uid), Some(gid)) => Ok(Some(Owner { uid, gid })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Sets the permission bits and, if given, the owner of a file.
+    pub async fn set_mode_and_owner(
+        &mut self,
+        path: impl PathType,
+        mode: u32,
+        owner: Option<Owner>,
+    ) -> Result<(), SSHErrors> {
+        let attributes = FileAttributes {
+            permissions: Some(mode),
+            uid: owner.map(|owner| owner.uid),
+            gid: owner.map(|owner| owner.gid),
+            ..FileAttributes::empty()
+        };
+        self.sftp.set_metadata(path.as_ref().to_string(), attributes).await?;
+        Ok(())
+    }
+
+    /// The permission bits and owner of a file.
+    pub async fn mode_and_owner(
+        &mut self,
+        path: impl PathType,
+    ) -> Result<(Option<u32>, Option<Owner>), SSHErrors> {
+        let metadata = self.sftp.metadata(path.as_ref().to_string()).await?;
+        let owner = metadata
+            .uid
+            .zip(metadata.gid)
+            .map(|(uid, gid)| Owner { uid, gid });
+        Ok((metadata.permissions.map(|mode| mode & 0o7777), owner))
+    }
+
+    pub async fn remove_file(&mut self, path: impl PathType) -> Result<(), SSHErrors> {
+        self.sftp.remove_file(path.as_ref().to_string()).await?;
+        Ok(())
+    }
+
     pub async fn list_dir(&mut self, path: impl PathType) -> Result<Vec<String>, SSHErrors> {
         let mut dir = self.sftp.read_dir(path.as_ref().to_string()).await?;
         let mut files = Vec::new();
@@ -95,6 +502,89 @@ impl SSHConnection {
         Ok(files)
     }
 
+    /// Every file and directory below `path`, parents before their children.
+    ///
+    /// Symlinks are listed but not followed.
+    pub async fn walk_dir(&mut self, path: impl PathType) -> Result<Vec<RemoteEntry>, SSHErrors> {
+        let mut entries = Vec::new();
+        let mut pending = vec![path.as_ref().to_string()];
+        while let Some(dir) = pending.pop() {
+            for entry in self.sftp.read_dir(dir.clone()).await? {
+                let name = entry.file_name();
+                if name == "." || name == ".." {
+                    continue;
+                }
+                let metadata = entry.metadata();
+                let entry = RemoteEntry {
+                    path: format!("{}/{}", dir.trim_end_matches('/'), name),
+                    file_type: metadata.file_type(),
+                    size: metadata.len(),
+                    mtime: metadata.mtime,
+                };
+                if entry.file_type == FileType::Dir {
+                    pending.push(entry.path.clone());
+                }
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Creates a directory and any missing parents, like `mkdir -p`.
+    pub async fn create_dir_all(&mut self, path: impl PathType) -> Result<(), SSHErrors> {
+        let path = path.as_ref().to_string();
+        let mut current = String::new();
+        for component in path.split('/') {
+            if component.is_empty() && current.is_empty() {
+                // an absolute path
+                current.push('/');
+                continue;
+            }
+            if component.is_empty() || component == "." {
+                continue;
+            }
+            if !current.is_empty() && !current.ends_with('/') {
+                current.push('/');
+            }
+            current.push_str(component);
+            if !self.sftp.try_exists(current.clone()).await? {
+                self.sftp.create_dir(current.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a directory and everything in it, like `rm -r`.
+    pub async fn remove_dir_all(&mut self, path: impl PathType) -> Result<(), SSHErrors> {
+        let path = path.as_ref().to_string();
+        let entries = self.walk_dir(path.as_str()).await?;
+        // children come after their parents, so remove back to front
+        for entry in entries.iter().rev() {
+            if entry.file_type == FileType::Dir {
+                self.sftp.remove_dir(entry.path.clone()).await?;
+            } else {
+                self.sftp.remove_file(entry.path.clone()).await?;
+            }
+        }
+        self.sftp.remove_dir(path).await?;
+        Ok(())
+    }
+
+    pub async fn rename(&mut self, from: impl PathType, to: impl PathType) -> Result<(), SSHErrors> {
+        self.sftp
+            .rename(from.as_ref().to_string(), to.as_ref().to_string())
+            .await?;
+        Ok(())
+    }
+
+    /// Creates a symlink at `link` pointing to `target`.
+    pub async fn symlink(&mut self, target: impl PathType, link: impl PathType) -> Result<(), SSHErrors> {
+        self.sftp
+            .symlink(link.as_ref().to_string(), target.as_ref().to_string())
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_file_type(&mut self, path: impl PathType) -> Result<FileType, SSHErrors> {
         let metadata = self.sftp.metadata(path.as_ref().to_string()).await?;
         Ok(metadata.file_type())
@@ -104,17 +594,31 @@ impl SSHConnection {
         let metadata = self.sftp.metadata(path.as_ref().to_string()).await?;
         Ok(metadata.len())
     }
+
+    /// The size of a remote file, with its permissions and modification time.
+    pub async fn get_file_meta(&mut self, path: impl PathType) -> Result<(u64, FileMeta), SSHErrors> {
+        let metadata = self.sftp.metadata(path.as_ref().to_string()).await?;
+        let meta = FileMeta {
+            mode: metadata.permissions,
+            mtime: metadata.mtime,
+        };
+        Ok((metadata.len(), meta))
+    }
 }
 
-pub async fn connect_ssh_client(config: ProjectConfig, descriptor: Descriptor) -> Result<SSHConnection, SSHErrors> {
-    let ipv4 = if let Some(ipv4) = config.address {
-        ipv4
-    } else {
-        let team = config.team.0;
-        Ipv4Addr::new(10, team as u8 / 100, team as u8 % 100, 2)
-    };
+#[async_trait::async_trait]
+impl RemoteShell for SSHConnection {
+    async fn exec(&mut self, command: &str, output: &mut Output<'_>) -> Result<u32, SSHErrors> {
+        self.call_with(command, output).await
+    }
+}
 
-    let ssh_config = russh::client::Config::default();
+pub async fn connect_ssh_client(ipv4: Ipv4Addr, descriptor: &Descriptor) -> Result<SSHConnection, SSHErrors> {
+    let ssh_config = russh::client::Config {
+        keepalive_interval: Some(KEEPALIVE_INTERVAL),
+        keepalive_max: KEEPALIVE_MAX,
+        ..Default::default()
+    };
     let ssh_client = SSHClientImpl {};
 
     info!("Connecting to {}...", ipv4);
@@ -127,7 +631,7 @@ pub async fn connect_ssh_client(config: ProjectConfig, descriptor: Descriptor) -
 
     info!("Connected to {}", ipv4);
 
-    if !ssh_session.authenticate_password(descriptor.root_user, descriptor.root_password).await? {
+    if !ssh_session.authenticate_password(&descriptor.root_user, &descriptor.root_password).await? {
         error!("Failed to authenticate as root");
         ssh_session.disconnect(
             Disconnect::AuthCancelledByUser,
@@ -152,4 +656,52 @@ pub async fn connect_ssh_client(config: ProjectConfig, descriptor: Descriptor) -
             file_buffers: HashMap::new()
         }
     )
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_copy_chunked_resumed() {
+        let data = (0..=255).collect::<Vec<u8>>();
+        let mut reader = io::Cursor::new(&data[100..]);
+        let mut writer = Vec::new();
+        let options = TransferOptions {
+            chunk_size: 64,
+            offset: 100,
+            preserve: None,
+        };
+        let mut reports = Vec::new();
+        let copied = copy_chunked(&mut reader, &mut writer, &options, Some(256), &mut |progress| {
+            reports.push(progress.transferred)
+        })
+        .await
+        .unwrap();
+        assert_eq!(copied, 156);
+        assert_eq!(writer, &data[100..]);
+        assert_eq!(reports, vec![164, 228, 256]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_resumed() {
+        let data = (0..=255).collect::<Vec<u8>>();
+        let mut reader = io::Cursor::new(data.clone());
+        // the upload was cut off after 100 bytes
+        let mut remote = io::Cursor::new(data[..100].to_vec());
+        let options = TransferOptions {
+            chunk_size: 100,
+            offset: 100,
+            preserve: None,
+        };
+        let mut reports = Vec::new();
+        let written = copy_from_offset(&mut reader, &mut remote, &options, Some(256), &mut |progress| {
+            reports.push((progress.transferred, progress.total))
+        })
+        .await
+        .unwrap();
+        assert_eq!(written, 156);
+        assert_eq!(remote.into_inner(), data);
+        assert_eq!(reports, vec![(200, Some(256)), (256, Some(256))]);
+    }
+}
@@ -1,10 +1,21 @@
-use std::{path::PathBuf, process::Command};
+use std::{
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
 
+use cargo_metadata::Message;
 use clap::Parser;
+use serde::Serialize;
 
 use crate::{
+    actions::{
+        git_commit::Provenance,
+        report::{self, Report},
+    },
     errors::CheckSubcommandError,
-    project_config::{self, ProjectConfig, Runtimes},
+    output,
+    project_config::{self, CheckTool, Lint, ProjectConfig, Runtimes},
 };
 
 const PACKAGE_SELECTION: &str = "Package Selection";
@@ -17,6 +28,16 @@ const COMMON_OPTIONS: &str = "Common Options";
 const USAGE: &str = "Usage Options";
 const DISPLAY_OPTIONS: &str = "Display Options";
 
+/// The FRC lints of the `lints` table and the clippy lint each one sets.
+const FRC_LINTS: [(&str, &str); 4] = [
+    ("unwrap", "clippy::unwrap_used"),
+    ("expect", "clippy::expect_used"),
+    ("panic", "clippy::panic"),
+    ("blocking-sleep", "clippy::disallowed_methods"),
+];
+/// The methods `blocking-sleep` disallows, they stall the robot loop.
+const BLOCKING_SLEEPS: [&str; 1] = ["std::thread::sleep"];
+
 #[derive(Parser)]
 pub struct Check {
     //# USAGE
@@ -29,6 +50,18 @@ pub struct Check {
     #[arg(short, long, help_heading = USAGE)]
     pub dev: bool,
 
+    /// Run `cargo check` or `cargo clippy`, defaults to the config's `default-tool` or clippy
+    #[arg(long, help_heading = USAGE)]
+    pub tool: Option<CheckTool>,
+
+    /// Check every combination of real and sim, with and without dev, and print a summary
+    #[arg(long, conflicts_with_all = ["sim", "real", "dev", "report", "message_format"], help_heading = USAGE)]
+    pub matrix: bool,
+
+    /// Also check every combination with these features, can be given several times
+    #[arg(long, value_name = "FEATURES", requires = "matrix", help_heading = USAGE)]
+    pub matrix_features: Vec<String>,
+
     //# COMMON OPTIONS
     #[arg(long, value_name = "KEY=VALUE", help_heading = COMMON_OPTIONS)]
     pub config: Vec<String>,
@@ -105,18 +138,28 @@ pub struct Check {
     pub offline: bool,
 
     //# DISPLAY OPTIONS
-    #[arg(short, long, help_heading = DISPLAY_OPTIONS)]
+    /// Set from the global `-v`
+    #[arg(skip)]
     pub verbose: bool,
 
-    #[arg(short, long, help_heading = DISPLAY_OPTIONS)]
+    /// Set from the global `-q`
+    #[arg(skip)]
     pub quiet: bool,
 
+    /// Set from the global `--json`
+    #[arg(skip)]
+    pub json: bool,
+
     #[arg(long, value_name = "WHEN", help_heading = DISPLAY_OPTIONS)]
     pub color: Option<String>,
 
-    #[arg(long, value_name = "FORMAT", help_heading = DISPLAY_OPTIONS)]
+    #[arg(long, value_name = "FORMAT", conflicts_with = "json", help_heading = DISPLAY_OPTIONS)]
     pub message_format: Option<String>,
 
+    /// Write a junit or json report of the diagnostics to PATH
+    #[arg(long, num_args = 2, value_names = ["FORMAT", "PATH"], conflicts_with = "message_format", help_heading = DISPLAY_OPTIONS)]
+    pub report: Option<Vec<String>>,
+
     //# MISC OPTIONS
     #[arg(short, long, value_name = "N", help_heading = MISC_OPTIONS)]
     pub jobs: Option<u64>,
@@ -128,55 +171,150 @@ pub struct Check {
     pub future_incompat_report: bool,
 }
 
-fn cmd_config(cmd: &mut Command, cfg: &str) {
+pub fn cmd_config(cmd: &mut Command, cfg: &str) {
     cmd.arg("--config");
     cmd.arg(format!("build.rustflags = [\"--cfg\", \"{}\"]", cfg));
 }
 
+fn lint_config(cmd: &mut Command, flag: &str, lint: &str) {
+    cmd.arg("--config");
+    cmd.arg(format!("build.rustflags = [\"{}\", \"{}\"]", flag, lint));
+}
+
 #[track_caller]
 pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubcommandError> {
+    if check.matrix {
+        return check_matrix(&check, &config, &Provenance::head_or_unknown());
+    }
+
+    let report = check
+        .report
+        .as_deref()
+        .map(report::parse_option)
+        .transpose()
+        .map_err(CheckSubcommandError::InvalidReport)?;
+
+    let mode = if check.sim {
+        Runtimes::Sim
+    } else if check.real {
+        Runtimes::Real
+    } else {
+        config.default_check
+    };
+
+    let (mut cmd, cfgs) = check_command(
+        &check,
+        &config,
+        &Provenance::head_or_unknown(),
+        mode,
+        check.dev,
+        check.features.as_deref(),
+    )?;
+
+    if report.is_some() || check.json {
+        cmd.arg("--message-format=json");
+    }
+
+    tracing::debug!("{:?}", cmd);
+
+    if report.is_some() || check.json {
+        let mut results = Report::new("check", &cfgs, mode.name(), check.dev);
+        let result = check_with_report(cmd, &mut results, true);
+        results.success = result.is_ok();
+        if let Some((format, path)) = report {
+            results
+                .write(&path, format)
+                .map_err(|source| CheckSubcommandError::WriteReport {
+                    path: path.clone(),
+                    source,
+                })?;
+            tracing::info!("Wrote the check report to {}", path.display());
+        }
+        if check.json {
+            output::print_json(&results);
+        }
+        return result;
+    }
+
+    let exit_status = cmd
+        .spawn()
+        .map_err(CheckSubcommandError::FailedToSpawnCargoCheck)?
+        .wait()
+        .map_err(CheckSubcommandError::FailedToWaitForCargoCheck)?;
+
+    if exit_status.success() {
+        Ok(())
+    } else {
+        Err(CheckSubcommandError::FailedCargoCheck {
+            code: exit_status.code(),
+        })
+    }
+}
+
+/// The `cargo check` or `cargo clippy` command for one runtime, with or without dev, and the cfgs it sets.
+///
+/// `features` is passed as `--features` in place of the one given on the command line.
+fn check_command(
+    check: &Check,
+    config: &ProjectConfig,
+    provenance: &Provenance,
+    mode: Runtimes,
+    dev: bool,
+    features: Option<&str>,
+) -> Result<(Command, Vec<&'static str>), CheckSubcommandError> {
+    let tool = check.tool.unwrap_or(config.default_tool);
     let mut cmd = Command::new(std::env::var("CARGO").unwrap_or("cargo".into()));
-    cmd.arg("clippy");
+    cmd.arg(tool.name());
 
     cmd.env("FRC_TEAM_NUMBER", config.team.0.to_string());
+    provenance.apply_env(&mut cmd);
 
+    let mut cfgs = vec![project_config::DEFAULT_CFG];
     cmd_config(&mut cmd, project_config::DEFAULT_CFG);
 
-    if check.dev {
+    if dev {
+        cfgs.push(project_config::DEV_CFG);
         cmd_config(&mut cmd, project_config::DEV_CFG);
     };
 
-    let mode: Runtimes;
-    if check.sim {
-        mode = Runtimes::Sim;
-    } else if check.real {
-        mode = Runtimes::Real;
-    } else {
-        mode = config.default_check;
-    }
-
-    match mode {
+    let target_dir = match mode {
         Runtimes::Real => {
+            cfgs.push(project_config::RUNTIME_REAL_CFG);
             cmd_config(&mut cmd, project_config::RUNTIME_REAL_CFG);
-            if check.dev {
-                cmd.arg("--target-dir");
-                cmd.arg(config.target_dirs.real_dev);
+            if dev {
+                &config.target_dirs.real_dev
             } else {
-                cmd.arg("--target-dir");
-                cmd.arg(config.target_dirs.real);
+                &config.target_dirs.real
             }
         }
         Runtimes::Sim => {
+            cfgs.push(project_config::RUNTIME_SIM_CFG);
             cmd_config(&mut cmd, project_config::RUNTIME_SIM_CFG);
-            if check.dev {
-                cmd.arg("--target-dir");
-                cmd.arg(config.target_dirs.sim_dev);
+            if dev {
+                &config.target_dirs.sim_dev
             } else {
-                cmd.arg("--target-dir");
-                cmd.arg(config.target_dirs.sim);
+                &config.target_dirs.sim
             }
         }
     };
+    cmd.arg("--target-dir");
+    cmd.arg(target_dir);
+
+    let mut blocking_sleep = false;
+    for lint in applied_lints(&config.lints, mode, tool) {
+        lint_config(&mut cmd, lint.level.flag(), lint_name(&lint.name));
+        blocking_sleep |= lint.name == "blocking-sleep";
+    }
+    if blocking_sleep {
+        let project_dir = match &check.manifest_path {
+            Some(manifest_path) => manifest_path.parent().map(Path::to_path_buf),
+            None => project_config::find_manifest()
+                .and_then(|manifest| manifest.parent().map(Path::to_path_buf)),
+        };
+        let conf_dir = target_dir.join("frc-clippy");
+        write_clippy_conf(project_dir.as_deref(), &conf_dir)?;
+        cmd.env("CLIPPY_CONF_DIR", conf_dir);
+    }
 
     if !check.config.is_empty() {
         cmd.arg("--config");
@@ -192,12 +330,12 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         });
     }
 
-    if let Some(dir) = check.change_dir {
+    if let Some(dir) = &check.change_dir {
         cmd.arg("-C");
         cmd.arg(dir);
     }
 
-    if let Some(package) = check.package {
+    if let Some(package) = &check.package {
         cmd.arg("--package");
         if let Some(package) = package {
             cmd.arg(package);
@@ -219,21 +357,21 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         cmd.arg("--lib");
     }
 
-    if let Some(bin) = check.bin {
+    if let Some(bin) = &check.bin {
         cmd.arg("--bin");
         if let Some(bin) = bin {
             cmd.arg(bin);
         }
     }
 
-    if let Some(example) = check.example {
+    if let Some(example) = &check.example {
         cmd.arg("--example");
         if let Some(example) = example {
             cmd.arg(example);
         }
     }
 
-    if let Some(test) = check.test {
+    if let Some(test) = &check.test {
         cmd.arg("--test");
         if let Some(test) = test {
             cmd.arg(test);
@@ -244,14 +382,14 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         cmd.arg("--tests");
     }
 
-    if let Some(bench) = check.bench {
+    if let Some(bench) = &check.bench {
         cmd.arg("--bench");
         if let Some(bench) = bench {
             cmd.arg(bench);
         }
     }
 
-    if let Some(features) = check.features {
+    if let Some(features) = features {
         cmd.arg("-F");
         cmd.arg(features);
     }
@@ -268,12 +406,12 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         cmd.arg("--release");
     }
 
-    if let Some(profile) = check.profile {
+    if let Some(profile) = &check.profile {
         cmd.arg("--profile");
         cmd.arg(profile);
     }
 
-    if let Some(target) = check.target {
+    if let Some(target) = &check.target {
         cmd.arg("--target");
         cmd.arg(target);
     }
@@ -282,7 +420,7 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         cmd.arg("--all-targets");
     }
 
-    if let Some(path) = check.manifest_path {
+    if let Some(path) = &check.manifest_path {
         cmd.arg("--manifest-path");
         cmd.arg(path);
     }
@@ -307,12 +445,12 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         cmd.arg("--quiet");
     }
 
-    if let Some(color) = check.color {
+    if let Some(color) = &check.color {
         cmd.arg("--color");
         cmd.arg(color);
     }
 
-    if let Some(format) = check.message_format {
+    if let Some(format) = &check.message_format {
         cmd.arg("--message-format");
         cmd.arg(format);
     }
@@ -330,14 +468,113 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         cmd.arg("--future-incompat-report");
     }
 
-    tracing::debug!("{:?}", cmd);
+    Ok((cmd, cfgs))
+}
 
-    let exit_status = cmd
+/// The lints of the `lints` table that apply to `mode`,
+/// lints only clippy knows are left out when running `cargo check`.
+fn applied_lints(lints: &[Lint], mode: Runtimes, tool: CheckTool) -> impl Iterator<Item = &Lint> {
+    lints.iter().filter(move |lint| {
+        let runtime = match lint.runtime {
+            Some(runtime) => runtime == mode,
+            None => true,
+        };
+        runtime && (tool == CheckTool::Clippy || !lint_name(&lint.name).starts_with("clippy::"))
+    })
+}
+
+/// The rustc or clippy name of a lint of the `lints` table.
+fn lint_name(name: &str) -> &str {
+    FRC_LINTS
+        .iter()
+        .find(|(frc, _)| *frc == name)
+        .map(|(_, lint)| *lint)
+        .unwrap_or(name)
+}
+
+/// Writes a `clippy.toml` to `conf_dir` that is the project's own with the blocking sleeps disallowed.
+fn write_clippy_conf(
+    project_dir: Option<&Path>,
+    conf_dir: &Path,
+) -> Result<(), CheckSubcommandError> {
+    let existing = project_dir.and_then(|dir| {
+        ["clippy.toml", ".clippy.toml"]
+            .map(|name| dir.join(name))
+            .into_iter()
+            .find(|path| path.is_file())
+    });
+    let conf = match &existing {
+        Some(path) => {
+            std::fs::read_to_string(path).map_err(|err| CheckSubcommandError::ClippyConf {
+                path: path.clone(),
+                source: err.into(),
+            })?
+        }
+        None => String::new(),
+    };
+    let conf =
+        disallow_blocking_sleeps(&conf).map_err(|source| CheckSubcommandError::ClippyConf {
+            path: existing.unwrap_or_else(|| conf_dir.join("clippy.toml")),
+            source,
+        })?;
+
+    let path = conf_dir.join("clippy.toml");
+    std::fs::create_dir_all(conf_dir)
+        .and_then(|_| std::fs::write(&path, conf))
+        .map_err(|err| CheckSubcommandError::ClippyConf {
+            path,
+            source: err.into(),
+        })
+}
+
+/// Adds [`BLOCKING_SLEEPS`] to the `disallowed-methods` of a clippy config.
+fn disallow_blocking_sleeps(
+    conf: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut conf = toml::from_str::<toml::Table>(conf)?;
+    let methods = conf
+        .entry("disallowed-methods")
+        .or_insert_with(|| toml::Value::Array(Vec::new()))
+        .as_array_mut()
+        .ok_or("`disallowed-methods` is not an array")?;
+    for method in BLOCKING_SLEEPS {
+        let mut entry = toml::Table::new();
+        entry.insert("path".to_owned(), method.into());
+        entry.insert(
+            "reason".to_owned(),
+            "it blocks the robot loop, see the `blocking-sleep` lint of the frc config".into(),
+        );
+        methods.push(entry.into());
+    }
+    Ok(toml::to_string(&conf)?)
+}
+
+/// Runs the check with json messages, printing the diagnostics as cargo would and recording them.
+///
+/// Without `echo` the diagnostics are only recorded.
+fn check_with_report(
+    mut cmd: Command,
+    report: &mut Report,
+    echo: bool,
+) -> Result<(), CheckSubcommandError> {
+    let mut child = cmd
+        .stdout(Stdio::piped())
         .spawn()
-        .map_err(|_| CheckSubcommandError::FailedToSpawnCargoCheck)?
-        .wait()
-        .map_err(|_| CheckSubcommandError::FailedToWaitForCargoCheck)?;
+        .map_err(CheckSubcommandError::FailedToSpawnCargoCheck)?;
+
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    for message in Message::parse_stream(stdout).flatten() {
+        if let Message::CompilerMessage(message) = message {
+            if let Some(rendered) = message.message.rendered.as_ref().filter(|_| echo) {
+                eprint!("{}", rendered);
+            }
+            report.add_diagnostic(&message.message);
+        }
+    }
 
+    let exit_status = child
+        .wait()
+        .map_err(CheckSubcommandError::FailedToWaitForCargoCheck)?;
     if exit_status.success() {
         Ok(())
     } else {
@@ -346,3 +583,167 @@ pub fn cargo_check(check: Check, config: ProjectConfig) -> Result<(), CheckSubco
         })
     }
 }
+
+/// The outcome of one combination of `--matrix`, a check report with the features it was checked with.
+#[derive(Serialize)]
+struct MatrixResult<'a> {
+    features: Option<&'a str>,
+    #[serde(flatten)]
+    report: Report,
+}
+
+/// Checks every runtime with and without dev, for the command line's features and each `--matrix-features`.
+///
+/// Every runtime and dev pair has its own target dir, so the pairs are checked in parallel,
+/// while the feature sets of a pair share it and are checked one after another.
+fn check_matrix(
+    check: &Check,
+    config: &ProjectConfig,
+    provenance: &Provenance,
+) -> Result<(), CheckSubcommandError> {
+    let mut feature_sets = vec![check.features.as_deref()];
+    feature_sets.extend(
+        check
+            .matrix_features
+            .iter()
+            .map(|features| Some(features.as_str())),
+    );
+    let pairs = [
+        (Runtimes::Real, false),
+        (Runtimes::Real, true),
+        (Runtimes::Sim, false),
+        (Runtimes::Sim, true),
+    ];
+
+    let lanes = std::thread::scope(|scope| {
+        let handles = pairs.map(|(mode, dev)| {
+            let feature_sets = &feature_sets;
+            scope.spawn(move || {
+                let mut results = Vec::with_capacity(feature_sets.len());
+                for features in feature_sets {
+                    let (mut cmd, cfgs) =
+                        check_command(check, config, provenance, mode, dev, *features)?;
+                    // the checks run side by side, cargo's progress would interleave
+                    cmd.arg("--message-format=json");
+                    cmd.arg("--quiet");
+                    tracing::debug!("{:?}", cmd);
+                    let mut report = Report::new("check", &cfgs, mode.name(), dev);
+                    match check_with_report(cmd, &mut report, false) {
+                        Ok(()) => report.success = true,
+                        Err(CheckSubcommandError::FailedCargoCheck { .. }) => {}
+                        Err(err) => return Err(err),
+                    }
+                    results.push(MatrixResult {
+                        features: *features,
+                        report,
+                    });
+                }
+                Ok(results)
+            })
+        });
+        handles.map(|handle| handle.join().expect("a matrix check panicked"))
+    });
+    let mut results = Vec::new();
+    for lane in lanes {
+        results.extend(lane?);
+    }
+
+    for result in results.iter().filter(|result| !result.report.success) {
+        eprintln!("\n{}:", describe(result));
+        for diagnostic in &result.report.diagnostics {
+            if diagnostic.level == "error" {
+                eprint!(
+                    "{}",
+                    diagnostic
+                        .rendered
+                        .as_deref()
+                        .unwrap_or(&diagnostic.message)
+                );
+            }
+        }
+    }
+
+    let failed = results
+        .iter()
+        .filter(|result| !result.report.success)
+        .count();
+    if check.json {
+        output::print_json(&serde_json::json!({
+            "success": failed == 0,
+            "combinations": results,
+        }));
+    } else {
+        print_matrix(&results);
+    }
+
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(CheckSubcommandError::FailedMatrix {
+            failed,
+            total: results.len(),
+        })
+    }
+}
+
+fn print_matrix(results: &[MatrixResult<'_>]) {
+    println!();
+    println!("{:<8} {:<4} {:<24} RESULT", "RUNTIME", "DEV", "FEATURES");
+    for result in results {
+        let count = |level: &str| {
+            result
+                .report
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.level == level)
+                .count()
+        };
+        let (errors, warnings) = (count("error"), count("warning"));
+        let mut outcome = if result.report.success {
+            "ok"
+        } else {
+            "FAILED"
+        }
+        .to_owned();
+        if errors > 0 {
+            outcome.push_str(&format!(", {errors} errors"));
+        }
+        if warnings > 0 {
+            outcome.push_str(&format!(", {warnings} warnings"));
+        }
+        println!(
+            "{:<8} {:<4} {:<24} {}",
+            result.report.runtime,
+            if result.report.dev { "yes" } else { "no" },
+            result.features.unwrap_or("default"),
+            outcome
+        );
+    }
+}
+
+fn describe(result: &MatrixResult) -> String {
+    format!(
+        "{}{} with {} features",
+        result.report.runtime,
+        if result.report.dev { " dev" } else { "" },
+        result.features.unwrap_or("default")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disallow_blocking_sleeps() {
+        let conf = disallow_blocking_sleeps(
+            "msrv = \"1.70\"\ndisallowed-methods = [\"std::process::exit\"]\n",
+        )
+        .unwrap();
+        let conf = toml::from_str::<toml::Table>(&conf).unwrap();
+        assert_eq!(conf["msrv"].as_str(), Some("1.70"));
+        let methods = conf["disallowed-methods"].as_array().unwrap();
+        assert_eq!(methods[0].as_str(), Some("std::process::exit"));
+        assert_eq!(methods[1]["path"].as_str(), Some("std::thread::sleep"));
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::Local;
+use clap::{Parser, Subcommand};
+use serde_json::json;
+
+use crate::{output, project_config::ProjectConfig};
+
+#[derive(Subcommand)]
+pub enum Comp {
+    /// Show the active and next competition
+    #[clap(name = "status")]
+    Status(Status),
+}
+
+#[derive(Parser)]
+pub struct Status {}
+
+pub fn cargo_comp(command: Comp, config: ProjectConfig, json: bool) {
+    match command {
+        Comp::Status(_) if json => print_comp_status_json(config),
+        Comp::Status(_) => comp_status(config),
+    }
+}
+
+/// Prints the active and next competition, `null` when there is none.
+fn print_comp_status_json(config: ProjectConfig) {
+    let today = Local::now().date_naive();
+    let active = config.git.active(today).map(|competition| {
+        json!({
+            "acronym": competition.acronym,
+            "start": competition.start.to_string(),
+            "end": competition.end.to_string(),
+            "day": (today - competition.start).num_days() + 1,
+            "days": (competition.end - competition.start).num_days() + 1,
+            "branch": format!("comp/{}", competition.acronym),
+        })
+    });
+    let next = config.git.next(today).map(|competition| {
+        json!({
+            "acronym": competition.acronym,
+            "start": competition.start.to_string(),
+            "end": competition.end.to_string(),
+            "starts_in_days": (competition.start - today).num_days(),
+        })
+    });
+    output::print_json(&json!({ "active": active, "next": next }));
+}
+
+fn comp_status(config: ProjectConfig) {
+    let today = Local::now().date_naive();
+
+    if config.git.competitions.is_empty() {
+        println!("No competitions configured in [package.metadata.frc.git]");
+        return;
+    }
+
+    match config.git.active(today) {
+        Some(competition) => println!(
+            "Active: {} ({} to {}), day {} of {}, deploys are committed to comp/{}",
+            competition.acronym,
+            competition.start,
+            competition.end,
+            (today - competition.start).num_days() + 1,
+            (competition.end - competition.start).num_days() + 1,
+            competition.acronym,
+        ),
+        None => println!("Active: none"),
+    }
+
+    match config.git.next(today) {
+        Some(competition) => println!(
+            "Next:   {} ({} to {}), starts in {} days",
+            competition.acronym,
+            competition.start,
+            competition.end,
+            (competition.start - today).num_days(),
+        ),
+        None => println!("Next:   none"),
+    }
+}
@@ -0,0 +1,132 @@
+use clap::{Parser, Subcommand};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    output,
+    project_config::{ProjectConfig, Runtimes},
+};
+
+#[derive(Subcommand)]
+pub enum Config {
+    /// Print the effective project config
+    #[clap(name = "show")]
+    Show(Show),
+}
+
+#[derive(Parser)]
+pub struct Show {
+    /// Also print where each value came from
+    #[arg(long)]
+    pub origin: bool,
+}
+
+pub fn cargo_config(command: Config, config: ProjectConfig, json: bool) {
+    match command {
+        Config::Show(_) if json => print_config_json(config),
+        Config::Show(show) => show_config(show, config),
+    }
+}
+
+/// The keys of `config show`, `override-address` is only set when not derived from the team.
+const KEYS: [&str; 9] = [
+    "team",
+    "robots",
+    "robot",
+    "override-address",
+    "deploy-dir",
+    "deploy-files",
+    "default-check",
+    "default-tool",
+    "lints",
+];
+
+/// Prints the config with typed values and where each came from.
+fn print_config_json(config: ProjectConfig) {
+    let lints = config
+        .lints
+        .iter()
+        .map(|lint| {
+            let lint_json = json!({
+                "level": lint.level.name(),
+                "runtime": lint.runtime.map(|runtime| runtime.name()),
+            });
+            (lint.name.clone(), lint_json)
+        })
+        .collect::<Map<_, _>>();
+    let origins = KEYS
+        .iter()
+        .map(|key| (key.to_string(), Value::from(config.origin(key).to_string())))
+        .collect::<Map<_, _>>();
+    output::print_json(&json!({
+        "package": config.package,
+        "team": config.team.0,
+        "robots": config.robots.iter().map(|robot| &robot.name).collect::<Vec<_>>(),
+        "robot": config.robot,
+        "address": config.target_address(),
+        "override-address": config.address,
+        "deploy-dir": config.deploy_dir,
+        "deploy-files": config.deploy_files,
+        "default-check": config.default_check.name(),
+        "default-tool": config.default_tool.name(),
+        "lints": lints,
+        "origins": origins,
+    }));
+}
+
+fn show_config(show: Show, config: ProjectConfig) {
+    let robots = config
+        .robots
+        .iter()
+        .map(|robot| robot.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let default_check = match config.default_check {
+        Runtimes::Real => "real",
+        Runtimes::Sim => "sim",
+    };
+    let lints = config
+        .lints
+        .iter()
+        .map(|lint| match lint.runtime {
+            Some(runtime) => format!(
+                "{} = {{ level = \"{}\", runtime = \"{}\" }}",
+                lint.name,
+                lint.level.name(),
+                runtime.name()
+            ),
+            None => format!("{} = \"{}\"", lint.name, lint.level.name()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let address = match config.address {
+        Some(address) => address.to_string(),
+        None => format!("{} (from team)", config.target_address()),
+    };
+    let rows = [
+        ("package", config.package.clone()),
+        ("team", config.team.0.to_string()),
+        ("robots", format!("[{}]", robots)),
+        ("robot", config.robot.clone().unwrap_or_default()),
+        ("override-address", address),
+        ("deploy-dir", config.deploy_dir.clone().unwrap_or_default()),
+        (
+            "deploy-files",
+            config
+                .deploy_files
+                .as_ref()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+        ),
+        ("default-check", default_check.to_owned()),
+        ("default-tool", config.default_tool.name().to_owned()),
+        ("lints", format!("{{{}}}", lints)),
+    ];
+    let width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    for (key, value) in rows {
+        if show.origin && key != "package" {
+            println!("{key:width$} = {value}  # {}", config.origin(key));
+        } else {
+            println!("{key:width$} = {value}");
+        }
+    }
+}
@@ -0,0 +1,1122 @@
+use std::{
+    collections::BTreeSet,
+    io::BufReader,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Instant,
+};
+
+use cargo_metadata::Message;
+use chrono::{Local, Utc};
+use clap::Parser;
+use serde::Serialize;
+use tracing::Instrument;
+
+use crate::{
+    actions::{
+        deploy_descriptor::{self, Descriptor, HealthSignal},
+        deploy_lock::{DeployLock, DEPLOY_LOCK, STALE_AFTER_MINUTES},
+        deploy_manifest::{self, DeployManifest, DEPLOY_MANIFEST},
+        git_commit::{self, Provenance},
+        connections::{retry_target, ConnectionManager},
+        remote_log, remote_probe,
+        ssh_client::{self, FileMeta, Owner, RemoteShell, SSHConnection, TransferOptions},
+    },
+    cmds::checker::cmd_config,
+    errors::DeploySubcommandError,
+    output,
+    project_config::{self, ProjectConfig},
+};
+
+const PACKAGE_SELECTION: &str = "Package Selection";
+const FEATURE_SELECTION: &str = "Feature Selection";
+const MANIFEST_OPTIONS: &str = "Manifest Options";
+const USAGE: &str = "Usage Options";
+
+/// The permissions of the deployed binary and directories, executable by the run-as user.
+const BINARY_MODE: u32 = 0o755;
+/// The permissions of every other deployed file.
+const FILE_MODE: u32 = 0o644;
+/// Appended to the binary, manifest and deploy dir of the previous deploy, which a failed health check restores.
+const PREVIOUS_SUFFIX: &str = ".prev";
+
+#[derive(Parser)]
+pub struct Deploy {
+    //# USAGE
+    #[arg(short, long, help_heading = USAGE)]
+    pub dev: bool,
+
+    /// Don't commit the deployed code, even during a competition
+    #[arg(long, help_heading = USAGE)]
+    pub no_commit: bool,
+
+    /// Deploy even if the robot fails the pre-deploy checks of the deploy descriptor
+    #[arg(long, help_heading = USAGE)]
+    pub force: bool,
+
+    /// Deploy even while someone else holds the deploy lock of the robot
+    #[arg(long, help_heading = USAGE)]
+    pub steal_lock: bool,
+
+    //# PACKAGE SELECTION
+    #[arg(short, long, value_name = "SPEC", help_heading = PACKAGE_SELECTION)]
+    pub package: Option<String>,
+
+    //# FEATURE SELECTION
+    #[arg(short = 'F', long, value_name = "FEATURES", help_heading = FEATURE_SELECTION)]
+    pub features: Option<String>,
+
+    #[arg(long, help_heading = FEATURE_SELECTION)]
+    pub all_features: bool,
+
+    #[arg(long, help_heading = FEATURE_SELECTION)]
+    pub no_default_features: bool,
+
+    //# MANIFEST OPTIONS
+    #[arg(long, value_name = "PATH", help_heading = MANIFEST_OPTIONS)]
+    pub manifest_path: Option<PathBuf>,
+
+    #[arg(long, help_heading = MANIFEST_OPTIONS)]
+    pub frozen: bool,
+
+    #[arg(long, help_heading = MANIFEST_OPTIONS)]
+    pub locked: bool,
+
+    #[arg(long, help_heading = MANIFEST_OPTIONS)]
+    pub offline: bool,
+
+    /// Set from the global `--json`
+    #[arg(skip)]
+    pub json: bool,
+}
+
+/// The `--json` output of a deploy.
+#[derive(Serialize)]
+struct DeployResult {
+    /// The configured name of the robot deployed to, if it could be told.
+    robot: Option<String>,
+    serial: Option<String>,
+    address: Ipv4Addr,
+    /// The path of the binary on the robot.
+    binary: String,
+    /// Every file written on the robot, the binary, the manifest and the deploy dir.
+    files: Vec<String>,
+    bytes: u64,
+    /// Seconds from the start of the build to the robot program being started.
+    duration: f64,
+}
+
+/// The files written to the robot so far and their total size.
+#[derive(Default)]
+struct Uploaded {
+    files: Vec<String>,
+    bytes: u64,
+}
+
+pub fn cargo_deploy(deploy: Deploy, config: ProjectConfig) -> Result<(), DeploySubcommandError> {
+    let descriptor = deploy_descriptor::builtin_descriptor("rio")
+        .expect("the rio descriptor is builtin")?;
+    let runtime = tokio::runtime::Runtime::new().map_err(DeploySubcommandError::Runtime)?;
+    runtime.block_on(deploy_to_robot(deploy, config, descriptor))
+}
+
+async fn deploy_to_robot(
+    deploy: Deploy,
+    config: ProjectConfig,
+    descriptor: Descriptor,
+) -> Result<(), DeploySubcommandError> {
+    let start = Instant::now();
+    if let Some(dir) = config.deploy_files.as_deref().filter(|dir| !dir.is_dir()) {
+        return Err(DeploySubcommandError::MissingDeployFiles(dir.to_path_buf()));
+    }
+    let (robot, serial) = identify_robot(&config, &descriptor).await?;
+
+    let competition = config
+        .git
+        .active(Local::now().date_naive())
+        .map(|competition| competition.acronym.clone());
+    // the commit is of the snapshot the build is stamped with, and only lands on the branch once deployed
+    let committing = competition.as_deref().filter(|_| !deploy.no_commit);
+    let (provenance, competition_commit) =
+        git_commit::deploy_snapshot(committing, robot.as_deref())?;
+    if config.git.require_clean
+        && competition.is_none()
+        && (provenance.dirty || provenance.sha.is_none())
+    {
+        return Err(DeploySubcommandError::DirtyTree);
+    }
+
+    let cfgs = build_cfgs(&deploy);
+    let binary = tracing::info_span!("build")
+        .in_scope(|| cargo_build(&deploy, &config, &descriptor, &cfgs, &provenance))?;
+    let build_time = Local::now();
+    let read_err = |source| DeploySubcommandError::Read {
+        path: binary.clone(),
+        source,
+    };
+    let executable = tokio::fs::File::open(&binary).await.map_err(read_err)?;
+    // the executable bit has to survive the upload for the robot to run the binary
+    let meta = FileMeta::of_local(&binary).map_err(read_err)?;
+    let file_name = binary
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| config.package.clone());
+    let remote_path = format!("{}/{}", descriptor.path.trim_end_matches('/'), file_name);
+    let manifest_path = format!("{}/{}", descriptor.path.trim_end_matches('/'), DEPLOY_MANIFEST);
+    let lock = DeployLock::new(provenance.sha.clone());
+    let manifest = DeployManifest {
+        package: config.package.clone(),
+        binary: file_name.clone(),
+        team: config.team.0,
+        robot: robot.clone(),
+        build_time: build_time.to_rfc3339(),
+        cfgs: cfgs.iter().map(|cfg| cfg.to_string()).collect(),
+        deployed_by: deploy_manifest::deploying_user(),
+        git: provenance,
+        competition,
+        competition_commit: competition_commit
+            .as_ref()
+            .map(|commit| commit.id.to_string()),
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).expect("the manifest is serializable");
+
+    // every step asks for the session again, so one lost in between is reconnected,
+    // only uploads are repeated when the session is lost during them
+    let mut connections = ConnectionManager::new(&config, &descriptor);
+
+    let connection = connections
+        .target()
+        .await
+        .map_err(DeploySubcommandError::Connect)?;
+    let owner = match &descriptor.run_as {
+        Some(user) => Some(
+            connection
+                .lookup_user(user)
+                .await?
+                .ok_or_else(|| DeploySubcommandError::UnknownUser(user.clone()))?,
+        ),
+        None => None,
+    };
+
+    let lock_path = format!("{}/{}", descriptor.path.trim_end_matches('/'), DEPLOY_LOCK);
+    acquire_lock(connection, &lock_path, &lock, deploy.steal_lock).await?;
+
+    let release = Release {
+        binary: &binary,
+        executable,
+        meta,
+        file_name: &file_name,
+        remote_path: &remote_path,
+        manifest_path: &manifest_path,
+        manifest: &manifest,
+        owner,
+    };
+    let deployed = replace_release(
+        &mut connections,
+        &config,
+        &descriptor,
+        deploy.force,
+        release,
+    )
+    .await;
+
+    // a lock that can't be released expires on its own, it doesn't fail the deploy
+    let released = match connections.target().await {
+        Ok(connection) => release_lock(connection, &lock_path, &lock).await,
+        Err(err) => Err(DeploySubcommandError::Connect(err)),
+    };
+    if let Err(err) = released {
+        tracing::warn!(
+            "Could not release the deploy lock at {}: {}",
+            lock_path,
+            err
+        );
+    }
+    let uploaded = deployed?;
+
+    if let Some(commit) = &competition_commit {
+        match commit.publish() {
+            Ok(()) => tracing::info!(
+                "Committed the deployed code to {} as {}",
+                commit.branch(),
+                commit.id
+            ),
+            Err(err) => tracing::warn!(
+                "Deployed, but could not commit the deployed code to {}: {}",
+                commit.branch(),
+                err
+            ),
+        }
+    }
+
+    tracing::info!("Deployed {} to {}", file_name, robot.as_deref().unwrap_or("the robot"));
+    if deploy.json {
+        output::print_json(&DeployResult {
+            robot,
+            serial,
+            address: config.target_address(),
+            binary: remote_path,
+            files: uploaded.files,
+            bytes: uploaded.bytes,
+            duration: start.elapsed().as_secs_f64(),
+        });
+    }
+    Ok(())
+}
+
+/// What a deploy writes to the robot, prepared before the deploy lock is taken.
+struct Release<'a> {
+    binary: &'a Path,
+    executable: tokio::fs::File,
+    meta: FileMeta,
+    file_name: &'a str,
+    remote_path: &'a str,
+    manifest_path: &'a str,
+    manifest: &'a [u8],
+    /// The descriptor's `run_as` user, who the files are handed to.
+    owner: Option<Owner>,
+}
+
+/// Checks the target, stops the robot-code, uploads the release and starts it again,
+/// waiting for its health check. The caller holds the deploy lock meanwhile.
+async fn replace_release(
+    connections: &mut ConnectionManager<'_>,
+    config: &ProjectConfig,
+    descriptor: &Descriptor,
+    force: bool,
+    release: Release<'_>,
+) -> Result<Uploaded, DeploySubcommandError> {
+    let Release {
+        binary,
+        mut executable,
+        meta,
+        file_name,
+        remote_path,
+        manifest_path,
+        manifest,
+        owner,
+    } = release;
+    let connection = connections
+        .target()
+        .await
+        .map_err(DeploySubcommandError::Connect)?;
+    let failed = check_target(connection, descriptor, config.target_address()).await?;
+    if !failed.is_empty() {
+        if !force {
+            return Err(DeploySubcommandError::UnsafeTarget(failed));
+        }
+        for reason in &failed {
+            tracing::warn!("Deploying anyway, {}", reason);
+        }
+    }
+
+    let code = connection
+        .call(&descriptor.stop_cmd)
+        .instrument(tracing::info_span!("stop"))
+        .await?;
+    if code != 0 {
+        tracing::warn!(
+            "`{}` exited with {}, the robot code may not have been running",
+            descriptor.stop_cmd,
+            code
+        );
+    }
+
+    let rollback = descriptor
+        .health_check
+        .as_ref()
+        .is_some_and(|check| check.rollback);
+    // only a deploy dir this deploy writes to is kept, and never one holding the binary
+    let remote_dir = config
+        .deploy_files
+        .as_ref()
+        .map(|_| remote_deploy_dir(config, descriptor));
+    let kept = rollback.then(|| Kept {
+        files: [
+            Some(remote_path),
+            Some(manifest_path),
+            descriptor.robot_command.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+        dir: remote_dir
+            .as_deref()
+            .filter(|dir| !contains_path(dir, &descriptor.path)),
+    });
+    let uploaded = async {
+        let mut uploaded = Uploaded::default();
+        if let Some(kept) = &kept {
+            if let (None, Some(dir)) = (kept.dir, &remote_dir) {
+                tracing::warn!("{} holds the binary, it isn't kept for a rollback", dir);
+            }
+            let connection = connections
+                .target()
+                .await
+                .map_err(DeploySubcommandError::Connect)?;
+            kept.keep(connection).await?;
+        }
+
+        // whole files are written again from the start if the session drops during one
+        tracing::info!("Uploading {} to {}", binary.display(), remote_path);
+        let options = TransferOptions {
+            preserve: Some(meta),
+            ..TransferOptions::default()
+        };
+        let mut progress = ssh_client::progress_printer(file_name.to_owned());
+        uploaded.bytes += retry_target!(connections, |connection| {
+            connection
+                .upload_from(remote_path, &mut executable, &options, &mut progress)
+                .await
+        })?;
+        uploaded.files.push(remote_path.to_owned());
+        retry_target!(connections, |connection| {
+            connection.upload_file(manifest_path, manifest).await
+        })?;
+        uploaded.bytes += manifest.len() as u64;
+        uploaded.files.push(manifest_path.to_owned());
+        let connection = connections
+            .target()
+            .await
+            .map_err(DeploySubcommandError::Connect)?;
+        install(connection, remote_path, BINARY_MODE, owner).await?;
+        install(connection, manifest_path, FILE_MODE, owner).await?;
+
+        // `start_cmd` runs whatever this names, otherwise nothing or an old binary would start
+        if let Some(robot_command) = descriptor.robot_command.as_deref() {
+            let command = format!("{}\n", remote_log::quote_path(remote_path));
+            retry_target!(connections, |connection| {
+                connection
+                    .upload_file(robot_command, command.as_bytes())
+                    .await
+            })?;
+            uploaded.bytes += command.len() as u64;
+            uploaded.files.push(robot_command.to_owned());
+            let connection = connections
+                .target()
+                .await
+                .map_err(DeploySubcommandError::Connect)?;
+            install(connection, robot_command, BINARY_MODE, owner).await?;
+        }
+
+        if let (Some(deploy_files), Some(remote_dir)) =
+            (config.deploy_files.as_deref(), &remote_dir)
+        {
+            let connection = connections
+                .target()
+                .await
+                .map_err(DeploySubcommandError::Connect)?;
+            upload_dir(connection, deploy_files, remote_dir, owner, &mut uploaded).await?;
+        }
+        Ok::<_, DeploySubcommandError>(uploaded)
+    }
+    .instrument(tracing::info_span!("upload"))
+    .await;
+    let uploaded = match uploaded {
+        Ok(uploaded) => uploaded,
+        Err(err) => {
+            // the robot-code is stopped, it's started again however far the upload got
+            let restarted = match connections.target().await {
+                Ok(connection) => {
+                    restart_after_failure(connection, &descriptor.start_cmd, kept.as_ref()).await
+                }
+                Err(err) => Err(DeploySubcommandError::Connect(err)),
+            };
+            match restarted {
+                Ok(true) => tracing::warn!("Restored and started the previous release"),
+                Ok(false) => tracing::warn!("Started the robot-code again"),
+                Err(restart_err) => tracing::warn!(
+                    "Could not start the robot-code again, restart it: {}",
+                    restart_err
+                ),
+            }
+            return Err(err);
+        }
+    };
+
+    let connection = connections
+        .target()
+        .await
+        .map_err(DeploySubcommandError::Connect)?;
+    let log_offset = match descriptor.health_check.as_ref().map(|check| &check.signal) {
+        Some(HealthSignal::LogLine(_)) => {
+            remote_probe::file_len(connection, &descriptor.log_path).await?
+        }
+        _ => 0,
+    };
+    let code = connection
+        .call(&descriptor.start_cmd)
+        .instrument(tracing::info_span!("restart"))
+        .await?;
+    if code != 0 {
+        return Err(DeploySubcommandError::RemoteCommand {
+            cmd: descriptor.start_cmd.clone(),
+            code,
+        });
+    }
+
+    if let Some(check) = &descriptor.health_check {
+        tracing::info!(
+            "Waiting up to {}s for the robot-code to come up",
+            check.timeout.as_secs()
+        );
+        let target = remote_probe::HealthTarget {
+            addr: config.target_address(),
+            binary: file_name,
+            log_path: &descriptor.log_path,
+            log_offset,
+        };
+        let unhealthy =
+            remote_probe::wait_healthy(check, connection, &reqwest::Client::new(), &target)
+                .instrument(tracing::info_span!("health"))
+                .await?;
+        if let Some(reason) = unhealthy {
+            print_log_tail(connection, &descriptor.log_path, check.log_lines).await;
+            let rolled_back = match &kept {
+                Some(kept) => roll_back(connection, descriptor, kept).await?,
+                None => false,
+            };
+            return Err(DeploySubcommandError::Unhealthy {
+                reason,
+                rolled_back,
+            });
+        }
+        tracing::info!("The robot-code is up");
+    }
+    Ok(uploaded)
+}
+
+/// Takes the deploy lock at `path`, refusing while someone else holds a fresh one unless `steal`.
+///
+/// The lock file is created exclusively, so of two deploys starting at once only one gets it.
+/// A stale or stolen lock is moved aside before it's replaced, and put back if it changed
+/// since it was read, so of two deploys replacing the same lock only one gets it too.
+async fn acquire_lock(
+    connection: &mut SSHConnection,
+    path: &str,
+    lock: &DeployLock,
+    steal: bool,
+) -> Result<(), DeploySubcommandError> {
+    let contents = serde_json::to_vec_pretty(lock).expect("the lock is serializable");
+    if connection.create_new(path, &contents).await? {
+        return Ok(());
+    }
+
+    let held = connection.download_file(path).await?;
+    match serde_json::from_slice::<DeployLock>(&held) {
+        Ok(other) if !other.is_stale(Utc::now()) => {
+            if !steal {
+                return Err(locked(&other));
+            }
+            tracing::warn!("Stealing the deploy lock of {}", other.holder());
+        }
+        Ok(other) => tracing::warn!("Taking over the stale deploy lock of {}", other.holder()),
+        Err(_) => {
+            // a lock that was just created may not be written yet
+            let (_, meta) = connection.get_file_meta(path).await?;
+            let age = meta
+                .mtime
+                .map(|mtime| Utc::now().timestamp() - i64::from(mtime));
+            if !steal && age.is_some_and(|age| age < STALE_AFTER_MINUTES * 60) {
+                return Err(locked_by(&held));
+            }
+            tracing::warn!("Replacing the unreadable deploy lock at {}", path);
+        }
+    }
+
+    let aside = format!("{}.{}-{}", path, lock.host, std::process::id());
+    // if the lock is already gone another deploy replaced it, creating it below tells who won
+    if connection.rename(path, aside.as_str()).await.is_ok() {
+        let replaced = connection.download_file(aside.as_str()).await?;
+        if replaced != held {
+            connection.rename(aside.as_str(), path).await?;
+            return Err(locked_by(&replaced));
+        }
+        connection.remove_file(aside.as_str()).await?;
+    }
+    if connection.create_new(path, &contents).await? {
+        return Ok(());
+    }
+    Err(locked_by(&connection.download_file(path).await?))
+}
+
+fn locked(held: &DeployLock) -> DeploySubcommandError {
+    DeploySubcommandError::Locked {
+        holder: held.holder(),
+        minutes: held
+            .age(Utc::now())
+            .map_or(0, |age| age.num_minutes().max(0)),
+        expires: STALE_AFTER_MINUTES,
+    }
+}
+
+/// [`locked`] for the contents of a lock file, which may not be written completely yet.
+fn locked_by(contents: &[u8]) -> DeploySubcommandError {
+    match serde_json::from_slice::<DeployLock>(contents) {
+        Ok(held) => locked(&held),
+        Err(_) => DeploySubcommandError::Locked {
+            holder: "another deploy".to_owned(),
+            minutes: 0,
+            expires: STALE_AFTER_MINUTES,
+        },
+    }
+}
+
+/// Removes the deploy lock at `path`, unless another deploy stole it in the meantime.
+async fn release_lock(
+    connection: &mut SSHConnection,
+    path: &str,
+    lock: &DeployLock,
+) -> Result<(), DeploySubcommandError> {
+    if !connection.exists(path).await? {
+        return Ok(());
+    }
+    let held = serde_json::from_slice::<DeployLock>(&connection.download_file(path).await?);
+    match held {
+        Ok(held) if held == *lock => connection.remove_file(path).await?,
+        Ok(held) => tracing::warn!("{} took the deploy lock during the deploy", held.holder()),
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Runs the pre-deploy checks of the descriptor and returns why each failed one failed.
+///
+/// A check that can't tell fails too, except for the free space which `df` may not report.
+/// Without an `enabled` probe the deploy only warns that it can't tell whether the robot is enabled.
+async fn check_target(
+    connection: &mut SSHConnection,
+    descriptor: &Descriptor,
+    addr: Ipv4Addr,
+) -> Result<Vec<String>, DeploySubcommandError> {
+    let checks = &descriptor.pre_deploy_checks;
+    let client = reqwest::Client::new();
+    let mut failed = Vec::new();
+
+    if let Some(min) = checks.min_free_space {
+        match remote_probe::free_space(connection, &descriptor.path).await? {
+            Some(free) if free < min => failed.push(format!(
+                "only {} free under {}, at least {} is needed",
+                ssh_client::human_bytes(free as f64),
+                descriptor.path,
+                ssh_client::human_bytes(min as f64)
+            )),
+            Some(_) => {}
+            None => tracing::warn!("Could not tell the free space under {}", descriptor.path),
+        }
+    }
+
+    if let Some(max) = checks.max_clock_skew {
+        match remote_probe::clock_skew(connection).await {
+            Ok(skew) if skew.unsigned_abs() > max.as_secs() => failed.push(format!(
+                "the clock of the robot is {}s {} this computer's, at most {}s are allowed",
+                skew.unsigned_abs(),
+                if skew > 0 { "ahead of" } else { "behind" },
+                max.as_secs()
+            )),
+            Ok(_) => {}
+            Err(err) => failed.push(format!("could not read the clock of the robot: {err}")),
+        }
+    }
+
+    if let Some(image) = &checks.image {
+        match remote_probe::read(&image.probe, connection, &client, addr).await {
+            Ok(output) if image.pattern.is_match(&output) => {}
+            Ok(_) => failed.push(format!(
+                "the OS image of the robot does not match `{}`",
+                image.pattern
+            )),
+            Err(err) => failed.push(format!("could not read the OS image of the robot: {err}")),
+        }
+    }
+
+    if let Some(enabled) = &checks.enabled {
+        match remote_probe::read(&enabled.probe, connection, &client, addr).await {
+            Ok(output) if enabled.pattern.is_match(&output) => {
+                failed.push("the robot is enabled".to_owned())
+            }
+            Ok(_) => {}
+            Err(err) => failed.push(format!(
+                "could not tell whether the robot is enabled: {err}"
+            )),
+        }
+    } else {
+        tracing::warn!(
+            "The descriptor can't tell whether the robot is enabled, make sure it is disabled before deploying"
+        );
+    }
+
+    Ok(failed)
+}
+
+/// What a deploy with `rollback` keeps as `.prev`, so a failed deploy can put it back.
+struct Kept<'a> {
+    /// The binary first, then the manifest and `robot_command`, moved aside.
+    files: Vec<&'a str>,
+    /// The deploy dir, copied rather than moved so unchanged files still aren't uploaded again.
+    /// Without a deploy dir there is no `.prev`, and restoring removes the one the deploy created.
+    dir: Option<&'a str>,
+}
+impl Kept<'_> {
+    /// Moves the files aside and copies the deploy dir, replacing what the deploy before kept.
+    async fn keep(&self, shell: &mut dyn RemoteShell) -> Result<(), DeploySubcommandError> {
+        for path in &self.files {
+            let (path, previous) = quoted_previous(path);
+            let command =
+                format!("rm -f {previous} && if [ -e {path} ]; then mv {path} {previous}; fi");
+            run(shell, &command).await?;
+        }
+        if let Some(dir) = self.dir {
+            let (dir, previous) = quoted_previous(dir);
+            let command =
+                format!("rm -rf {previous} && if [ -d {dir} ]; then cp -a {dir} {previous}; fi");
+            run(shell, &command).await?;
+        }
+        Ok(())
+    }
+
+    /// Whether there is a kept binary to go back to.
+    async fn has_previous(
+        &self,
+        shell: &mut dyn RemoteShell,
+    ) -> Result<bool, DeploySubcommandError> {
+        let (_, previous) = quoted_previous(self.files[0]);
+        let code = shell
+            .exec(&format!("[ -e {previous} ]"), &mut |_: &[u8]| {})
+            .await?;
+        Ok(code == 0)
+    }
+
+    /// Puts back what [`keep`](Self::keep) kept, files without a `.prev` are left as they are.
+    async fn restore(&self, shell: &mut dyn RemoteShell) -> Result<(), DeploySubcommandError> {
+        for path in &self.files {
+            let (path, previous) = quoted_previous(path);
+            let command =
+                format!("if [ -e {previous} ]; then rm -f {path} && mv {previous} {path}; fi");
+            run(shell, &command).await?;
+        }
+        if let Some(dir) = self.dir {
+            let (dir, previous) = quoted_previous(dir);
+            let command =
+                format!("rm -rf {dir} && if [ -d {previous} ]; then mv {previous} {dir}; fi");
+            run(shell, &command).await?;
+        }
+        Ok(())
+    }
+}
+
+/// `path` and the `<path>.prev` it's kept as, quoted for the shell.
+fn quoted_previous(path: &str) -> (String, String) {
+    (
+        remote_log::quote_path(path),
+        remote_log::quote_path(&format!("{path}{PREVIOUS_SUFFIX}")),
+    )
+}
+
+/// Puts what `kept` kept back and starts it, after the deployed robot-code failed its health check,
+/// returns whether there was a previous release to go back to.
+async fn roll_back(
+    shell: &mut dyn RemoteShell,
+    descriptor: &Descriptor,
+    kept: &Kept<'_>,
+) -> Result<bool, DeploySubcommandError> {
+    if !kept.has_previous(shell).await? {
+        tracing::warn!("There is no previous release on the robot to roll back to");
+        return Ok(false);
+    }
+    tracing::warn!("Rolling back to the previous release");
+    shell.exec(&descriptor.stop_cmd, &mut |_: &[u8]| {}).await?;
+    kept.restore(shell).await?;
+    run(shell, &descriptor.start_cmd).await?;
+    Ok(true)
+}
+
+/// Starts the robot-code again after the deploy failed while it was stopped,
+/// putting back what `kept` kept first if there is a previous release.
+/// Returns whether the previous release was restored.
+async fn restart_after_failure(
+    shell: &mut dyn RemoteShell,
+    start_cmd: &str,
+    kept: Option<&Kept<'_>>,
+) -> Result<bool, DeploySubcommandError> {
+    let restored = match kept {
+        Some(kept) if kept.has_previous(shell).await? => {
+            kept.restore(shell).await?;
+            true
+        }
+        _ => false,
+    };
+    run(shell, start_cmd).await?;
+    Ok(restored)
+}
+
+/// Runs `command` on the robot, failing if it exits with anything but 0.
+async fn run(shell: &mut dyn RemoteShell, command: &str) -> Result<(), DeploySubcommandError> {
+    match shell.exec(command, &mut |_: &[u8]| {}).await? {
+        0 => Ok(()),
+        code => Err(DeploySubcommandError::RemoteCommand {
+            cmd: command.to_owned(),
+            code,
+        }),
+    }
+}
+
+/// Whether the remote directory `dir` is `path` or one of its parents.
+fn contains_path(dir: &str, path: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    let path = path.trim_end_matches('/');
+    path == dir || path.starts_with(&format!("{dir}/"))
+}
+
+/// Prints the last `lines` lines of the robot-code's log to stderr, for a failed health check.
+async fn print_log_tail(connection: &mut SSHConnection, log_path: &str, lines: usize) {
+    eprintln!("The last {lines} lines of {log_path}:");
+    let command = remote_log::tail_command(log_path, false, Some(lines));
+    if let Err(err) =
+        remote_log::stream_lines(connection, &command, |line| eprintln!("{line}")).await
+    {
+        tracing::warn!("Could not read {}: {}", log_path, err);
+    }
+}
+
+/// Where `deploy-files` go on the robot, `deploy-dir` relative to the deploy user's home,
+/// or else the descriptor's `extra_files_path`.
+fn remote_deploy_dir(config: &ProjectConfig, descriptor: &Descriptor) -> String {
+    let home = descriptor.path.trim_end_matches('/');
+    match config.deploy_dir.as_deref() {
+        Some(dir) if dir.starts_with('/') => dir.trim_end_matches('/').to_owned(),
+        Some(dir) => format!(
+            "{}/{}",
+            home,
+            dir.trim_start_matches("./").trim_end_matches('/')
+        ),
+        None => format!(
+            "{}/{}",
+            home,
+            descriptor.extra_files_path.trim_start_matches("./")
+        ),
+    }
+}
+
+/// Copies the contents of `local_dir` into `remote_dir`, keeping executable files executable.
+///
+/// Files on the robot with the size and modification time of the local file aren't sent again.
+/// Files in `remote_dir` that aren't in `local_dir` are left alone, they weren't put there by this deploy.
+async fn upload_dir(
+    connection: &mut SSHConnection,
+    local_dir: &Path,
+    remote_dir: &str,
+    owner: Option<Owner>,
+    uploaded: &mut Uploaded,
+) -> Result<(), DeploySubcommandError> {
+    let files = local_files(local_dir).map_err(|source| DeploySubcommandError::Read {
+        path: local_dir.to_path_buf(),
+        source,
+    })?;
+
+    let existing = if connection.exists(remote_dir).await? {
+        connection.walk_dir(remote_dir).await?
+    } else {
+        Vec::new()
+    };
+    connection.create_dir_all(remote_dir).await?;
+    install(connection, remote_dir, BINARY_MODE, owner).await?;
+    // the directories on the way to the uploaded files, which the run-as user has to be able to enter
+    let mut dirs = BTreeSet::new();
+
+    tracing::info!("Syncing {} files from {} to {}", files.len(), local_dir.display(), remote_dir);
+    for file in files {
+        let read_err = |source| DeploySubcommandError::Read {
+            path: file.clone(),
+            source,
+        };
+        let relative = file
+            .strip_prefix(local_dir)
+            .expect("files are found below the deploy directory")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        let remote = format!("{}/{}", remote_dir, relative.join("/"));
+        for depth in 1..relative.len() {
+            let dir = format!("{}/{}", remote_dir, relative[..depth].join("/"));
+            if dirs.insert(dir.clone()) {
+                connection.create_dir_all(dir.as_str()).await?;
+            }
+        }
+
+        let meta = FileMeta::of_local(&file).map_err(read_err)?;
+        let len = std::fs::metadata(&file).map_err(read_err)?.len();
+        let unchanged = meta.mtime.is_some()
+            && existing.iter().any(|entry| {
+                entry.path == remote && entry.size == len && entry.mtime == meta.mtime
+            });
+        if !unchanged {
+            let reader = tokio::fs::File::open(&file).await.map_err(read_err)?;
+            let options = TransferOptions {
+                preserve: Some(meta),
+                ..TransferOptions::default()
+            };
+            uploaded.bytes += connection
+                .upload_from(remote.as_str(), reader, &options, &mut |_| {})
+                .await?;
+            uploaded.files.push(remote.clone());
+        }
+        let executable = meta.mode.is_some_and(|mode| mode & 0o111 != 0);
+        let mode = if executable { BINARY_MODE } else { FILE_MODE };
+        install(connection, &remote, mode, owner).await?;
+    }
+
+    for dir in dirs {
+        install(connection, &dir, BINARY_MODE, owner).await?;
+    }
+    Ok(())
+}
+
+/// Every file below `dir`, without the directories themselves.
+fn local_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Hands a deployed file to the run-as user with `mode` and checks that both stuck.
+async fn install(
+    connection: &mut SSHConnection,
+    path: &str,
+    mode: u32,
+    owner: Option<Owner>,
+) -> Result<(), DeploySubcommandError> {
+    connection.set_mode_and_owner(path, mode, owner).await?;
+    let (found_mode, found_owner) = connection.mode_and_owner(path).await?;
+    if found_mode != Some(mode) || (owner.is_some() && found_owner != owner) {
+        return Err(DeploySubcommandError::Permissions {
+            path: path.to_owned(),
+            expected: describe_permissions(Some(mode), owner),
+            found: describe_permissions(found_mode, found_owner),
+        });
+    }
+    Ok(())
+}
+
+fn describe_permissions(mode: Option<u32>, owner: Option<Owner>) -> String {
+    let mode = mode.map_or("unknown mode".to_owned(), |mode| format!("{mode:04o}"));
+    match owner {
+        Some(owner) => format!("{mode} owned by {}:{}", owner.uid, owner.gid),
+        None => mode,
+    }
+}
+
+/// Pings the robot and matches its serial number against the configured robots,
+/// returns the name of the robot and its serial number.
+///
+/// Falls back to the selected robot when the serial number can't be read,
+/// but refuses to deploy if an explicitly selected robot is not the one connected.
+async fn identify_robot(
+    config: &ProjectConfig,
+    descriptor: &Descriptor,
+) -> Result<(Option<String>, Option<String>), DeploySubcommandError> {
+    let addr = config.target_address();
+    let client = reqwest::Client::new();
+    descriptor
+        .pinger
+        .call(&client, addr)
+        .await
+        .map_err(DeploySubcommandError::Unreachable)?;
+
+    let serial = match descriptor.serial_getter.call(&client, addr).await {
+        Ok(serial) => serial,
+        Err(err) => {
+            tracing::warn!("Could not identify the robot at {}: {}", addr, err);
+            return Ok((config.robot.clone(), None));
+        }
+    };
+    let found = config
+        .robots
+        .iter()
+        .find(|robot| robot.serials.contains(&serial))
+        .map(|robot| robot.name.clone());
+
+    match (&config.robot, &found) {
+        (Some(expected), Some(found))
+            if expected != found && config.origins.contains_key("robot") =>
+        {
+            Err(DeploySubcommandError::WrongRobot {
+                expected: expected.clone(),
+                found: found.clone(),
+            })
+        }
+        (_, Some(_)) => Ok((found, Some(serial))),
+        (selected, None) => {
+            tracing::warn!("Robot with serial {} is not in the config", serial);
+            Ok((selected.clone(), Some(serial)))
+        }
+    }
+}
+
+/// The `--cfg` flags deployed code is built with.
+fn build_cfgs(deploy: &Deploy) -> Vec<&'static str> {
+    let mut cfgs = vec![project_config::DEFAULT_CFG, project_config::RUNTIME_REAL_CFG];
+    if deploy.dev {
+        cfgs.push(project_config::DEV_CFG);
+    }
+    cfgs
+}
+
+/// Builds the robot package for the real runtime and returns the path of its executable.
+fn cargo_build(
+    deploy: &Deploy,
+    config: &ProjectConfig,
+    descriptor: &Descriptor,
+    cfgs: &[&str],
+    provenance: &Provenance,
+) -> Result<PathBuf, DeploySubcommandError> {
+    let mut cmd = Command::new(std::env::var("CARGO").unwrap_or("cargo".into()));
+    cmd.arg("build");
+    cmd.arg("--release");
+    cmd.arg("--message-format=json-render-diagnostics");
+
+    cmd.env("FRC_TEAM_NUMBER", config.team.0.to_string());
+    provenance.apply_env(&mut cmd);
+
+    for cfg in cfgs {
+        cmd_config(&mut cmd, cfg);
+    }
+
+    cmd.arg("--target-dir");
+    if deploy.dev {
+        cmd.arg(&config.target_dirs.real_dev);
+    } else {
+        cmd.arg(&config.target_dirs.real);
+    }
+
+    cmd.arg("--target");
+    cmd.arg(&descriptor.rust_target);
+
+    cmd.arg("--package");
+    cmd.arg(&config.package);
+
+    if let Some(features) = &deploy.features {
+        cmd.arg("-F");
+        cmd.arg(features);
+    }
+
+    if deploy.all_features {
+        cmd.arg("--all-features");
+    }
+
+    if deploy.no_default_features {
+        cmd.arg("--no-default-features");
+    }
+
+    if let Some(path) = &deploy.manifest_path {
+        cmd.arg("--manifest-path");
+        cmd.arg(path);
+    }
+
+    if deploy.frozen {
+        cmd.arg("--frozen");
+    }
+
+    if deploy.locked {
+        cmd.arg("--locked");
+    }
+
+    if deploy.offline {
+        cmd.arg("--offline");
+    }
+
+    tracing::debug!("{:?}", cmd);
+
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(DeploySubcommandError::FailedToSpawnCargoBuild)?;
+
+    let mut executables = Vec::new();
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    for message in Message::parse_stream(stdout).flatten() {
+        if let Message::CompilerArtifact(artifact) = message {
+            if let Some(executable) = artifact.executable {
+                executables.push((artifact.target.name, executable.into_std_path_buf()));
+            }
+        }
+    }
+
+    let exit_status = child
+        .wait()
+        .map_err(DeploySubcommandError::FailedToWaitForCargoBuild)?;
+    if !exit_status.success() {
+        return Err(DeploySubcommandError::FailedCargoBuild {
+            code: exit_status.code(),
+        });
+    }
+
+    // prefer the binary named after the package if there are several
+    let position = executables
+        .iter()
+        .position(|(name, _)| *name == config.package)
+        .or((executables.len() == 1).then_some(0))
+        .ok_or_else(|| DeploySubcommandError::MissingExecutable(config.package.clone()))?;
+    Ok(executables.swap_remove(position).1)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::actions::remote_log::test::LocalShell;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_restart_after_failed_upload() {
+        let dir = std::env::temp_dir().join(format!("frc-rollback-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("deploy")).unwrap();
+        fs::write(dir.join("robot"), "old binary").unwrap();
+        fs::write(dir.join("deploy/auto.json"), "old auto").unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+        let (binary, robot_command, deploy_dir) =
+            (path("robot"), path("robotCommand"), path("deploy"));
+        let kept = Kept {
+            files: vec![&binary, &robot_command],
+            dir: Some(&deploy_dir),
+        };
+
+        kept.keep(&mut LocalShell).await.unwrap();
+        assert!(!dir.join("robot").exists());
+        // the upload failed after writing part of the deploy dir and no binary
+        fs::write(dir.join("deploy/auto.json"), "new auto").unwrap();
+        fs::write(dir.join("robotCommand"), "new command").unwrap();
+
+        let start = format!("touch {}", remote_log::quote_path(&path("started")));
+        let restored = restart_after_failure(&mut LocalShell, &start, Some(&kept))
+            .await
+            .unwrap();
+        assert!(restored);
+        assert_eq!(fs::read_to_string(dir.join("robot")).unwrap(), "old binary");
+        assert_eq!(
+            fs::read_to_string(dir.join("deploy/auto.json")).unwrap(),
+            "old auto"
+        );
+        // there was no robotCommand before, the new one is left alone
+        assert_eq!(
+            fs::read_to_string(dir.join("robotCommand")).unwrap(),
+            "new command"
+        );
+        assert!(dir.join("started").exists());
+        assert!(!dir.join("robot.prev").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
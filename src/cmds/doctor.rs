@@ -0,0 +1,437 @@
+use std::{
+    error::Error,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use clap::Parser;
+use serde::Serialize;
+
+use crate::{
+    actions::{
+        deploy_descriptor::{self, Descriptor},
+        remote_probe,
+        ssh_client::{self, SSHErrors},
+    },
+    configs::layers::ConfigOverrides,
+    errors::{DoctorSubcommandError, ExitCategory, RuntimeError},
+    output,
+    project_config::{self, ProjectConfig},
+};
+
+/// Free space under the deploy path below which a deploy is likely to fail,
+/// for descriptors without a `min_free_space_mb` pre-deploy check.
+const MIN_DISK_SPACE: u64 = 20 * 1024 * 1024;
+/// Free space under the deploy path below which a few more deploys or logs fill the robot,
+/// raised to the descriptor's minimum when that is higher.
+const LOW_DISK_SPACE: u64 = 100 * 1024 * 1024;
+
+#[derive(Parser)]
+pub struct Doctor {
+    #[arg(short, long, value_name = "SPEC")]
+    pub package: Option<String>,
+
+    #[arg(long, value_name = "PATH")]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Set from the global `--json`
+    #[arg(skip)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One item of the checklist.
+#[derive(Serialize)]
+struct Item {
+    name: String,
+    status: Status,
+    detail: String,
+    /// How to fix a warning or failure.
+    hint: Option<String>,
+}
+
+/// The items checked so far, printed as they are checked unless the output is `--json`.
+struct Checklist {
+    items: Vec<Item>,
+    json: bool,
+    /// What kind of problem the first failed item was, doctor exits with it.
+    failure: Option<ExitCategory>,
+}
+impl Checklist {
+    fn add(&mut self, name: &str, status: Status, detail: String, hint: Option<String>) {
+        if !self.json {
+            let mark = match status {
+                Status::Pass => "ok",
+                Status::Warn => "warn",
+                Status::Fail => "FAIL",
+            };
+            println!("[{mark:^4}] {name}: {detail}");
+            if let Some(hint) = &hint {
+                println!("       help: {hint}");
+            }
+        }
+        self.items.push(Item {
+            name: name.to_owned(),
+            status,
+            detail,
+            hint,
+        });
+    }
+
+    fn pass(&mut self, name: &str, detail: String) {
+        self.add(name, Status::Pass, detail, None);
+    }
+
+    fn warn(&mut self, name: &str, detail: String, hint: String) {
+        self.add(name, Status::Warn, detail, Some(hint));
+    }
+
+    fn fail(&mut self, name: &str, category: ExitCategory, detail: String, hint: Option<String>) {
+        self.failure.get_or_insert(category);
+        self.add(name, Status::Fail, detail, hint);
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.status == status)
+            .count()
+    }
+}
+
+pub fn cargo_doctor(
+    doctor: Doctor,
+    overrides: &ConfigOverrides,
+) -> Result<(), DoctorSubcommandError> {
+    let mut list = Checklist {
+        items: Vec::new(),
+        json: doctor.json,
+        failure: None,
+    };
+
+    let config = check_config(&mut list, overrides, &doctor);
+    let descriptors = check_descriptors(&mut list);
+
+    let mut targets = descriptors
+        .iter()
+        .map(|descriptor| descriptor.rust_target.as_str())
+        .collect::<Vec<_>>();
+    targets.sort_unstable();
+    targets.dedup();
+    for target in targets {
+        check_rustup_target(&mut list, target);
+        check_linker(&mut list, target, doctor.manifest_path.as_deref());
+    }
+
+    // the robots are reached the way every other command does, with the rio descriptor
+    let rio = deploy_descriptor::builtin_descriptor("rio").and_then(Result::ok);
+    if let (Some(config), Some(descriptor)) = (&config, &rio) {
+        let runtime = tokio::runtime::Runtime::new().map_err(DoctorSubcommandError::Runtime)?;
+        runtime.block_on(check_robots(&mut list, config, descriptor));
+    }
+
+    let failed = list.count(Status::Fail);
+    if doctor.json {
+        output::print_json(&serde_json::json!({
+            "success": failed == 0,
+            "checks": list.items,
+        }));
+    } else {
+        println!(
+            "\n{} passed, {} warnings, {} failed",
+            list.count(Status::Pass),
+            list.count(Status::Warn),
+            failed
+        );
+    }
+
+    match list.failure {
+        None => Ok(()),
+        Some(category) => Err(DoctorSubcommandError::Failed {
+            failed,
+            total: list.items.len(),
+            category,
+        }),
+    }
+}
+
+/// The error and its causes on one line, leaving out causes its message already includes.
+fn describe(err: &dyn Error) -> String {
+    let mut detail = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        let cause_detail = cause.to_string();
+        if !detail.contains(&cause_detail) {
+            detail.push_str(": ");
+            detail.push_str(&cause_detail);
+        }
+        source = cause.source();
+    }
+    detail
+}
+
+fn check_config(
+    list: &mut Checklist,
+    overrides: &ConfigOverrides,
+    doctor: &Doctor,
+) -> Option<ProjectConfig> {
+    match project_config::read_config2(
+        overrides,
+        doctor.manifest_path.as_deref(),
+        doctor.package.as_deref(),
+    ) {
+        Ok(config) => {
+            list.pass(
+                "config",
+                format!("{} for team {}", config.package, config.team.0),
+            );
+            Some(config)
+        }
+        Err(err) => {
+            let detail = describe(&err);
+            list.fail(
+                "config",
+                ExitCategory::Config,
+                detail,
+                RuntimeError::Config(err).help(),
+            );
+            None
+        }
+    }
+}
+
+/// Loads every builtin descriptor and returns the ones that are valid.
+fn check_descriptors(list: &mut Checklist) -> Vec<Descriptor> {
+    let mut valid = Vec::new();
+    for (name, descriptor) in deploy_descriptor::builtin_descriptors() {
+        let item = format!("descriptor {name}");
+        match descriptor {
+            Ok(descriptor) => {
+                list.pass(
+                    &item,
+                    format!(
+                        "deploys to {} for {}",
+                        descriptor.path, descriptor.rust_target
+                    ),
+                );
+                valid.push(descriptor);
+            }
+            Err(err) => list.fail(
+                &item,
+                ExitCategory::Config,
+                describe(&err),
+                Some("this descriptor ships with cargo-frc, reinstall or update it".to_owned()),
+            ),
+        }
+    }
+    valid
+}
+
+fn check_rustup_target(list: &mut Checklist, target: &str) {
+    let item = format!("rust target {target}");
+    let output = match Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            list.warn(
+                &item,
+                "could not run `rustup target list --installed`".to_owned(),
+                format!("install rustup from https://rustup.rs, or the standard library for {target} by other means"),
+            );
+            return;
+        }
+    };
+    if String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| line.trim() == target)
+    {
+        list.pass(&item, "installed".to_owned());
+    } else {
+        list.fail(
+            &item,
+            ExitCategory::Config,
+            "not installed".to_owned(),
+            Some(format!("run `rustup target add {target}`")),
+        );
+    }
+}
+
+fn check_linker(list: &mut Checklist, target: &str, manifest_path: Option<&Path>) {
+    let item = format!("linker for {target}");
+    let Some((linker, origin)) = configured_linker(target, manifest_path) else {
+        list.warn(
+            &item,
+            "none found in the environment or a cargo config, cargo may link with the host's `cc`"
+                .to_owned(),
+            format!("install the WPILib roboRIO toolchain and set `linker` in `[target.{target}]` of .cargo/config.toml"),
+        );
+        return;
+    };
+    match find_program(&linker) {
+        Some(path) => list.pass(&item, format!("{} from {}", path.display(), origin)),
+        None => list.fail(
+            &item,
+            ExitCategory::Config,
+            format!("`{linker}` from {origin} is not installed or not on PATH"),
+            Some(format!(
+                "install the toolchain that provides `{linker}`, or fix {origin}"
+            )),
+        ),
+    }
+}
+
+/// The linker cargo uses for `target` and where it's set, from the environment or a cargo config.
+///
+/// This is a best-effort check, the cargo configs are searched from the manifest's directory
+/// like cargo does when run there, and only `[target.<triple>]` tables are looked at.
+/// `[target.'cfg(..)']` tables, `--config` and `.cargo/config*` above another cwd are not.
+fn configured_linker(target: &str, manifest_path: Option<&Path>) -> Option<(String, String)> {
+    let var = format!(
+        "CARGO_TARGET_{}_LINKER",
+        target.to_uppercase().replace(['-', '.'], "_")
+    );
+    if let Ok(linker) = std::env::var(&var) {
+        return Some((linker, format!("`{var}`")));
+    }
+
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")));
+    let manifest = match manifest_path {
+        Some(manifest_path) => std::env::current_dir().ok()?.join(manifest_path),
+        None => project_config::find_manifest()?,
+    };
+    let dirs = manifest
+        .parent()?
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .chain(cargo_home);
+    for dir in dirs {
+        for name in ["config.toml", "config"] {
+            let path = dir.join(name);
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(table) = contents.parse::<toml::Table>() else {
+                continue;
+            };
+            if let Some(linker) = table
+                .get("target")
+                .and_then(|targets| targets.get(target))
+                .and_then(|target| target.get("linker"))
+                .and_then(toml::Value::as_str)
+            {
+                return Some((linker.to_owned(), path.display().to_string()));
+            }
+        }
+    }
+    None
+}
+
+/// Where `program` is, either as a path or found on `PATH`.
+fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file() || candidate.with_extension("exe").is_file())
+}
+
+/// Pings every configured robot, logs in over SSH and checks the free space under the deploy path.
+async fn check_robots(list: &mut Checklist, config: &ProjectConfig, descriptor: &Descriptor) {
+    let mut robots = config
+        .robots
+        .iter()
+        .map(|robot| (robot.name.clone(), config.robot_address(robot)))
+        .collect::<Vec<_>>();
+    if robots.is_empty() {
+        robots.push(("robot".to_owned(), config.target_address()));
+    }
+
+    let client = reqwest::Client::new();
+    for (name, addr) in robots {
+        if let Err(err) = descriptor.pinger.call(&client, addr).await {
+            list.fail(
+                &format!("{name} at {addr}"),
+                ExitCategory::Unreachable,
+                format!("unreachable, {}", describe(&err)),
+                Some(
+                    "connect to the robot's network, or check `team` and `override-address`"
+                        .to_owned(),
+                ),
+            );
+            continue;
+        }
+        list.pass(&format!("{name} at {addr}"), "reachable".to_owned());
+        check_ssh(list, &name, addr, descriptor).await;
+    }
+}
+
+async fn check_ssh(list: &mut Checklist, name: &str, addr: Ipv4Addr, descriptor: &Descriptor) {
+    let item = format!("{name} ssh");
+    let mut connection = match ssh_client::connect_ssh_client(addr, descriptor).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            let hint = match err {
+                SSHErrors::SSHError(russh::Error::NotAuthenticated) => format!(
+                    "the robot refused `{}` with the descriptor's password, reset it by reimaging the robot",
+                    descriptor.root_user
+                ),
+                _ => "make sure SSH is enabled on the robot and nothing else is on its address"
+                    .to_owned(),
+            };
+            list.fail(&item, ExitCategory::Unreachable, describe(&err), Some(hint));
+            return;
+        }
+    };
+    list.pass(&item, format!("logged in as {}", descriptor.root_user));
+
+    let item = format!("{name} disk space");
+    match remote_probe::free_space(&mut connection, &descriptor.path).await {
+        Ok(Some(free)) => {
+            let detail = format!(
+                "{} free under {}",
+                ssh_client::human_bytes(free as f64),
+                descriptor.path
+            );
+            let hint = "delete old logs, e.g. with `cargo frc fetch-logs --delete`".to_owned();
+            // the same minimum a deploy checks before replacing the code
+            let min = descriptor
+                .pre_deploy_checks
+                .min_free_space
+                .unwrap_or(MIN_DISK_SPACE);
+            if free < min {
+                list.fail(&item, ExitCategory::Deploy, detail, Some(hint));
+            } else if free < LOW_DISK_SPACE.max(min) {
+                list.warn(&item, detail, hint);
+            } else {
+                list.pass(&item, detail);
+            }
+        }
+        Ok(None) => list.warn(
+            &item,
+            format!(
+                "`df` could not tell the free space under {}",
+                descriptor.path
+            ),
+            "check the deploy path of the descriptor exists on the robot".to_owned(),
+        ),
+        Err(err) => list.warn(
+            &item,
+            describe(&err),
+            "the connection was lost, run doctor again".to_owned(),
+        ),
+    }
+}
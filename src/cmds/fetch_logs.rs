@@ -0,0 +1,242 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use clap::Parser;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    actions::{
+        deploy_descriptor::{self, Descriptor},
+        connections::ConnectionManager,
+        ssh_client::{self, FileMeta, SSHConnection, TransferOptions},
+    },
+    errors::FetchLogsSubcommandError,
+    output,
+    project_config::ProjectConfig,
+};
+
+/// The event name used for logs fetched outside of a configured competition.
+const NO_EVENT: &str = "practice";
+
+#[derive(Parser)]
+pub struct FetchLogs {
+    /// Delete the logs from the robot once their download is verified
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Where to archive the logs, in `<DIR>/<robot>/<event>/`
+    #[arg(long, value_name = "DIR", default_value = "logs")]
+    pub dir: PathBuf,
+
+    #[arg(short, long, value_name = "SPEC")]
+    pub package: Option<String>,
+
+    #[arg(long, value_name = "PATH")]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Set from the global `--json`
+    #[arg(skip)]
+    pub json: bool,
+}
+
+pub fn cargo_fetch_logs(
+    fetch: FetchLogs,
+    config: ProjectConfig,
+) -> Result<(), FetchLogsSubcommandError> {
+    let descriptor = deploy_descriptor::builtin_descriptor("rio")
+        .expect("the rio descriptor is builtin")?;
+    let runtime = tokio::runtime::Runtime::new().map_err(FetchLogsSubcommandError::Runtime)?;
+    runtime.block_on(fetch_logs(fetch, config, descriptor))
+}
+
+async fn fetch_logs(
+    fetch: FetchLogs,
+    config: ProjectConfig,
+    descriptor: Descriptor,
+) -> Result<(), FetchLogsSubcommandError> {
+    let addr = config.target_address();
+    let client = reqwest::Client::new();
+    descriptor
+        .pinger
+        .call(&client, addr)
+        .await
+        .map_err(FetchLogsSubcommandError::Unreachable)?;
+    let robot = match descriptor.serial_getter.call(&client, addr).await {
+        Ok(serial) => config
+            .robot_with_serial(&serial)
+            .map(|robot| robot.name.clone()),
+        Err(err) => {
+            tracing::warn!("Could not identify the robot at {}: {}", addr, err);
+            None
+        }
+    }
+    .or_else(|| config.robot.clone())
+    .unwrap_or_else(|| "unknown".to_owned());
+    let event = config
+        .git
+        .active(Local::now().date_naive())
+        .map(|competition| competition.acronym.clone())
+        .unwrap_or_else(|| NO_EVENT.to_owned());
+
+    let dest = fetch.dir.join(&robot).join(&event);
+    std::fs::create_dir_all(&dest).map_err(|source| FetchLogsSubcommandError::Io {
+        path: dest.clone(),
+        source,
+    })?;
+
+    let mut connections = ConnectionManager::new(&config, &descriptor);
+
+    let (mut fetched, mut skipped, mut deleted) = (0, 0, 0);
+    for glob in &descriptor.datalog_globs {
+        let connection = connections
+            .get(addr)
+            .await
+            .map_err(FetchLogsSubcommandError::Connect)?;
+        // a log directory on a usb stick is missing whenever the stick is
+        let names = match connection.list_dir(glob.dir.as_str()).await {
+            Ok(names) => names,
+            Err(err) => {
+                tracing::debug!("Skipping {}: {}", glob.dir, err);
+                continue;
+            }
+        };
+        for name in names.iter().filter(|name| glob.pattern.matches(name)) {
+            let remote = format!("{}/{}", glob.dir.trim_end_matches('/'), name);
+            let local = dest.join(name);
+            // a long download is the likeliest to be cut off, pick the session up again per log
+            let connection = connections
+                .get(addr)
+                .await
+                .map_err(FetchLogsSubcommandError::Connect)?;
+            if fetch_log(connection, &remote, &local).await? {
+                fetched += 1;
+            } else {
+                skipped += 1;
+            }
+
+            if fetch.delete {
+                if verify(connection, &remote, &local).await? {
+                    connection.remove_file(remote.as_str()).await?;
+                    deleted += 1;
+                } else {
+                    tracing::warn!("Keeping {} on the robot, it differs from {}", remote, local.display());
+                }
+            }
+        }
+    }
+
+    tracing::info!(
+        "Fetched {} logs into {}, {} already archived{}",
+        fetched,
+        dest.display(),
+        skipped,
+        if fetch.delete {
+            format!(", deleted {} from the robot", deleted)
+        } else {
+            String::new()
+        }
+    );
+    if fetch.json {
+        output::print_json(&serde_json::json!({
+            "robot": robot,
+            "event": event,
+            "dir": dest,
+            "fetched": fetched,
+            "skipped": skipped,
+            "deleted": deleted,
+        }));
+    }
+    Ok(())
+}
+
+/// Downloads `remote` to `local` unless it is already there, returns whether anything was fetched.
+///
+/// A log is already there when the archived copy has its size and modification time,
+/// which the download carries over from the robot.
+/// The download goes to `<local>.part` first and resumes from there if it was interrupted.
+/// A log that grew since it was archived, like the one being written right now, is resumed too.
+async fn fetch_log(
+    connection: &mut SSHConnection,
+    remote: &str,
+    local: &Path,
+) -> Result<bool, FetchLogsSubcommandError> {
+    let io_err = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| FetchLogsSubcommandError::Io { path, source }
+    };
+
+    let (size, meta) = connection.get_file_meta(remote).await?;
+    let archived = std::fs::metadata(local).map(|meta| meta.len()).ok();
+    let archived_mtime = FileMeta::of_local(local).ok().and_then(|meta| meta.mtime);
+    if archived == Some(size) && archived_mtime == meta.mtime {
+        return Ok(false);
+    }
+
+    let mut part = local.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    if archived.is_some() {
+        std::fs::rename(local, &part).map_err(io_err(local))?;
+    }
+    let mut offset = std::fs::metadata(&part).map(|meta| meta.len()).unwrap_or(0);
+    // the log was replaced by another one with the same name, start over
+    if offset > size || archived == Some(size) {
+        offset = 0;
+    }
+
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(offset > 0)
+        .write(true)
+        .truncate(offset == 0)
+        .open(&part)
+        .await
+        .map_err(io_err(&part))?;
+    let options = TransferOptions {
+        offset,
+        ..TransferOptions::default()
+    };
+    let mut progress = ssh_client::progress_printer(remote.to_owned());
+    let meta = connection
+        .download_to(remote, file, &options, &mut progress)
+        .await?;
+    // keep the robot's timestamps, they are when the match was played
+    meta.apply_local(&part).map_err(io_err(&part))?;
+    std::fs::rename(&part, local).map_err(io_err(local))?;
+    Ok(true)
+}
+
+/// Whether the archived copy has the same sha256 as the log on the robot.
+async fn verify(
+    connection: &mut SSHConnection,
+    remote: &str,
+    local: &Path,
+) -> Result<bool, FetchLogsSubcommandError> {
+    let io_err = |source| FetchLogsSubcommandError::Io {
+        path: local.to_path_buf(),
+        source,
+    };
+    // logs can be larger than is worth holding in memory, hash them as they are read
+    let mut hasher = Sha256::new();
+    let mut file = std::fs::File::open(local).map_err(io_err)?;
+    std::io::copy(&mut file, &mut hasher).map_err(io_err)?;
+    let local_hash = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    let mut output = Vec::new();
+    let code = connection
+        .call_with(
+            &format!("sha256sum '{}'", remote.replace('\'', r"'\''")),
+            &mut |data: &[u8]| output.extend_from_slice(data),
+        )
+        .await?;
+    if code != 0 {
+        tracing::warn!("Could not hash {} on the robot, `sha256sum` exited with {}", remote, code);
+        return Ok(false);
+    }
+    let output = String::from_utf8_lossy(&output);
+    Ok(output.split_whitespace().next() == Some(local_hash.as_str()))
+}
@@ -0,0 +1,159 @@
+use std::{
+    fs::File,
+    io::{IsTerminal, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use clap::Parser;
+use regex::Regex;
+
+use crate::{
+    actions::{
+        connections::ConnectionManager,
+        deploy_descriptor,
+        remote_log::{self, Level, LogFilter},
+    },
+    errors::LogsSubcommandError,
+    output,
+    project_config::ProjectConfig,
+};
+
+const FILTER_OPTIONS: &str = "Filter Options";
+const DISPLAY_OPTIONS: &str = "Display Options";
+
+#[derive(Parser)]
+pub struct Logs {
+    /// Keep printing new lines as the robot-code writes them
+    #[arg(short, long)]
+    pub follow: bool,
+
+    /// How many lines from the end of the log to start at
+    #[arg(short = 'n', long, default_value_t = 100, conflicts_with = "since")]
+    pub lines: usize,
+
+    //# FILTER OPTIONS
+    /// Only show entries since a timestamp or a duration ago, e.g. `2024-03-01T12:00:00Z` or `10m`
+    #[arg(long, value_name = "WHEN", value_parser = parse_since, help_heading = FILTER_OPTIONS)]
+    pub since: Option<DateTime<FixedOffset>>,
+
+    /// Only show entries of this level or above
+    #[arg(short, long, help_heading = FILTER_OPTIONS)]
+    pub level: Option<Level>,
+
+    /// Only show lines matching this regex
+    #[arg(short, long, value_name = "REGEX", help_heading = FILTER_OPTIONS)]
+    pub grep: Option<Regex>,
+
+    //# DISPLAY OPTIONS
+    #[arg(long, help_heading = DISPLAY_OPTIONS)]
+    pub no_color: bool,
+
+    /// Also write the shown lines to a timestamped file under `<target-dir>/real/logs`
+    #[arg(long, help_heading = DISPLAY_OPTIONS)]
+    pub save: bool,
+
+    #[arg(short, long, value_name = "SPEC")]
+    pub package: Option<String>,
+
+    #[arg(long, value_name = "PATH")]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Set from the global `--json`, prints every line as `{"line", "level"}`
+    #[arg(skip)]
+    pub json: bool,
+}
+
+fn parse_since(value: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time);
+    }
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("{value:?} is missing a unit, one of s, m, h or d"))?;
+    let (amount, unit) = value.split_at(split);
+    let amount = amount
+        .parse::<i64>()
+        .map_err(|_| format!("{value:?} is not a timestamp or a duration like `10m`"))?;
+    let ago = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        _ => return Err(format!("{unit:?} is not a unit, use one of s, m, h or d")),
+    };
+    Ok((Local::now() - ago).fixed_offset())
+}
+
+pub fn cargo_logs(logs: Logs, config: ProjectConfig) -> Result<(), LogsSubcommandError> {
+    let descriptor = deploy_descriptor::builtin_descriptor("rio")
+        .expect("the rio descriptor is builtin")?;
+    let runtime = tokio::runtime::Runtime::new().map_err(LogsSubcommandError::Runtime)?;
+    runtime.block_on(async {
+        let mut connections = ConnectionManager::new(&config, &descriptor);
+        let connection = connections
+            .target()
+            .await
+            .map_err(LogsSubcommandError::Connect)?;
+
+        let mut save = logs.save.then(|| save_file(&config)).transpose()?;
+        let color = !logs.no_color && std::io::stdout().is_terminal();
+        let lines = logs.since.is_none().then_some(logs.lines);
+        let command = remote_log::tail_command(&descriptor.log_path, logs.follow, lines);
+        tracing::info!("Reading {} on {}", descriptor.log_path, connection.addr());
+        let mut filter = LogFilter::new(logs.level, logs.grep, logs.since);
+        let mut stdout = std::io::stdout();
+        let mut save_error = None;
+
+        let stream = remote_log::stream_lines(connection, &command, |line| {
+            let (shown, level) = filter.check(line);
+            if !shown {
+                return;
+            }
+            if logs.json {
+                output::print_json(&serde_json::json!({ "line": line, "level": level }));
+            } else if color {
+                let _ = writeln!(stdout, "{}", remote_log::colorize(line, level));
+            } else {
+                let _ = writeln!(stdout, "{}", line);
+            }
+            if let Some((path, file)) = &mut save {
+                if let Err(source) = writeln!(file, "{}", line) {
+                    save_error.get_or_insert(LogsSubcommandError::Save {
+                        path: path.clone(),
+                        source,
+                    });
+                }
+            }
+        });
+
+        let code = tokio::select! {
+            code = stream => code?,
+            // following only ends when interrupted
+            _ = tokio::signal::ctrl_c() => 0,
+        };
+        if let Some(err) = save_error {
+            return Err(err);
+        }
+        if code != 0 {
+            return Err(LogsSubcommandError::RemoteCommand { cmd: command, code });
+        }
+        if let Some((path, _)) = save {
+            tracing::info!("Saved the log to {}", path.display());
+        }
+        Ok(())
+    })
+}
+
+fn save_file(config: &ProjectConfig) -> Result<(PathBuf, File), LogsSubcommandError> {
+    let dir = config.target_dirs.real.join("logs");
+    let path = dir.join(format!(
+        "{}-{}.log",
+        config.robot.as_deref().unwrap_or("robot"),
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| File::create(&path))
+        .map(|file| (path.clone(), file))
+        .map_err(|source| LogsSubcommandError::Save { path, source })
+}
@@ -1,3 +1,9 @@
 pub mod checker;
 pub mod tester;
-pub mod deployer;
\ No newline at end of file
+pub mod deployer;
+pub mod configurer;
+pub mod competition;
+pub mod status;
+pub mod logs;
+pub mod fetch_logs;
+pub mod doctor;
\ No newline at end of file
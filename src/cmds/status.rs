@@ -0,0 +1,291 @@
+use std::{net::Ipv4Addr, path::PathBuf};
+
+use clap::Parser;
+use serde::Serialize;
+
+use crate::{
+    actions::{
+        connections::ConnectionManager,
+        deploy_descriptor::{self, Descriptor},
+        deploy_manifest::{DeployManifest, DEPLOY_MANIFEST},
+        git_commit::{self, Provenance},
+    },
+    errors::StatusSubcommandError,
+    output,
+    project_config::ProjectConfig,
+};
+
+#[derive(Parser)]
+pub struct Status {
+    #[arg(short, long, value_name = "SPEC")]
+    pub package: Option<String>,
+
+    #[arg(long, value_name = "PATH")]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Set from the global `--json`
+    #[arg(skip)]
+    pub json: bool,
+}
+
+pub fn cargo_status(status: Status, config: ProjectConfig) -> Result<(), StatusSubcommandError> {
+    let descriptor = deploy_descriptor::builtin_descriptor("rio")
+        .expect("the rio descriptor is builtin")?;
+    let runtime = tokio::runtime::Runtime::new().map_err(StatusSubcommandError::Runtime)?;
+    let reachable = runtime.block_on(status_of_robots(config, descriptor, status.json));
+    if reachable.is_empty() {
+        return Err(StatusSubcommandError::Unreachable);
+    }
+    Ok(())
+}
+
+/// Where the local tree is, to compare deploys against.
+struct Local {
+    repo: Option<gix::Repository>,
+    head: Option<gix::ObjectId>,
+    provenance: Provenance,
+}
+
+/// What was found at the address of a robot, the `--json` output per robot.
+#[derive(Serialize)]
+struct RobotStatus {
+    /// The robot identified by its serial, or the robots configured with the address.
+    robot: String,
+    address: Ipv4Addr,
+    reachable: bool,
+    serial: Option<String>,
+    /// Why the robot was unreachable or its manifest couldn't be read.
+    error: Option<String>,
+    /// The manifest of the deployed code, `None` if nothing was deployed by cargo-frc.
+    deployed: Option<DeployManifest>,
+    /// How the deployed code relates to the local HEAD and work tree.
+    compared_to_local: Option<String>,
+}
+
+/// Prints the status of every configured robot and returns the addresses that could be reached.
+async fn status_of_robots(
+    config: ProjectConfig,
+    descriptor: Descriptor,
+    json: bool,
+) -> Vec<Ipv4Addr> {
+    let local = local_tree();
+    if !json {
+        print_local(&local.provenance);
+    }
+
+    // robots sharing an address are told apart by their serial once reached
+    let mut addresses = Vec::<(Ipv4Addr, Vec<&str>)>::new();
+    for robot in &config.robots {
+        let addr = config.robot_address(robot);
+        match addresses.iter_mut().find(|(other, _)| *other == addr) {
+            Some((_, names)) => names.push(&robot.name),
+            None => addresses.push((addr, vec![&robot.name])),
+        }
+    }
+    if addresses.is_empty() {
+        addresses.push((config.target_address(), Vec::new()));
+    }
+
+    let client = reqwest::Client::new();
+    let mut connections = ConnectionManager::new(&config, &descriptor);
+    let mut robots = Vec::new();
+    let mut reachable = Vec::new();
+    for (addr, names) in addresses {
+        let names = if names.is_empty() {
+            "robot".to_owned()
+        } else {
+            names.join("/")
+        };
+        let mut status = RobotStatus {
+            robot: names,
+            address: addr,
+            reachable: false,
+            serial: None,
+            error: None,
+            deployed: None,
+            compared_to_local: None,
+        };
+        match descriptor.pinger.call(&client, addr).await {
+            Ok(_) => status.reachable = true,
+            Err(err) => status.error = Some(err.to_string()),
+        }
+        if status.reachable {
+            reachable.push(addr);
+            if let Ok(serial) = descriptor.serial_getter.call(&client, addr).await {
+                status.robot = config
+                    .robot_with_serial(&serial)
+                    .map(|robot| robot.name.clone())
+                    .unwrap_or_else(|| format!("unknown robot with serial {serial}"));
+                status.serial = Some(serial);
+            }
+            match read_manifest(&mut connections, addr, &descriptor).await {
+                Ok(manifest) => {
+                    status.compared_to_local = manifest
+                        .as_ref()
+                        .map(|manifest| compare(&manifest.git, &local));
+                    status.deployed = manifest;
+                }
+                Err(err) => status.error = Some(err.to_string()),
+            }
+        }
+
+        if json {
+            robots.push(status);
+        } else {
+            print_robot(&status);
+        }
+    }
+
+    if json {
+        output::print_json(&serde_json::json!({
+            "local": local.provenance,
+            "robots": robots,
+        }));
+    }
+    reachable
+}
+
+fn local_tree() -> Local {
+    let repo = match git_commit::open_repo() {
+        Ok(repo) => repo,
+        Err(err) => {
+            tracing::warn!("Could not open the local git repository: {}", err);
+            return Local {
+                repo: None,
+                head: None,
+                provenance: Provenance::UNKNOWN,
+            };
+        }
+    };
+    let head = git_commit::head_id(&repo).unwrap_or_else(|err| {
+        tracing::warn!("Could not resolve HEAD: {}", err);
+        None
+    });
+    let provenance = git_commit::provenance(&repo).unwrap_or_else(|err| {
+        tracing::warn!("Could not determine the state of the work tree: {}", err);
+        Provenance::UNKNOWN
+    });
+    Local {
+        repo: Some(repo),
+        head,
+        provenance,
+    }
+}
+
+/// Downloads the deploy manifest, `None` if the robot has none.
+async fn read_manifest(
+    connections: &mut ConnectionManager<'_>,
+    addr: Ipv4Addr,
+    descriptor: &Descriptor,
+) -> Result<Option<DeployManifest>, Box<dyn std::error::Error>> {
+    let connection = connections.get(addr).await?;
+    let dir = descriptor.path.trim_end_matches('/');
+    if !connection
+        .list_dir(dir)
+        .await?
+        .iter()
+        .any(|name| name == DEPLOY_MANIFEST)
+    {
+        return Ok(None);
+    }
+    let data = connection
+        .download_file(format!("{dir}/{DEPLOY_MANIFEST}").as_str())
+        .await?;
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+fn print_local(provenance: &Provenance) {
+    println!(
+        "Local: {} on {}{}",
+        short_sha(provenance.sha.as_deref()),
+        provenance.branch.as_deref().unwrap_or("a detached HEAD"),
+        if provenance.dirty { ", with uncommitted changes" } else { "" },
+    );
+}
+
+fn print_robot(status: &RobotStatus) {
+    println!();
+    if !status.reachable {
+        println!(
+            "{} ({}): unreachable, {}",
+            status.robot,
+            status.address,
+            status.error.as_deref().unwrap_or_default()
+        );
+        return;
+    }
+    println!("{} ({}):", status.robot, status.address);
+    match (&status.deployed, &status.error) {
+        (Some(manifest), _) => print_deployed(manifest, status.compared_to_local.as_deref()),
+        (None, Some(err)) => println!("  failed to read {}: {}", DEPLOY_MANIFEST, err),
+        (None, None) => println!("  nothing deployed by cargo-frc"),
+    }
+}
+
+fn print_deployed(manifest: &DeployManifest, compared_to_local: Option<&str>) {
+    let git = &manifest.git;
+    println!(
+        "  deployed: {} ({}) on {}{}",
+        manifest.binary,
+        manifest.package,
+        short_sha(git.sha.as_deref()),
+        git.branch
+            .as_deref()
+            .map(|branch| format!(" from {branch}"))
+            .unwrap_or_default(),
+    );
+    println!("  built:    {}", manifest.build_time);
+    println!("  by:       {}", manifest.deployed_by);
+    println!("  cfgs:     {}", manifest.cfgs.join(" "));
+    if let Some(competition) = &manifest.competition {
+        println!(
+            "  at:       {}{}",
+            competition,
+            manifest
+                .competition_commit
+                .as_deref()
+                .map(|commit| format!(", committed as {}", short_sha(Some(commit))))
+                .unwrap_or_default(),
+        );
+    }
+    if let Some(compared) = compared_to_local {
+        println!("  compared to local: {}", compared);
+    }
+}
+
+/// Describes how a deploy relates to the local HEAD and work tree.
+fn compare(deployed: &Provenance, local: &Local) -> String {
+    let (Some(sha), Some(repo), Some(head)) = (&deployed.sha, &local.repo, local.head) else {
+        return "unknown, the deploy or the local tree has no git history".to_owned();
+    };
+
+    let commits = if *sha == head.to_string() {
+        "same commit as HEAD".to_owned()
+    } else {
+        let behind = git_commit::commits_between(repo, sha, head).ok().flatten();
+        let ahead = gix::ObjectId::from_hex(sha.as_bytes())
+            .ok()
+            .and_then(|deployed| git_commit::commits_between(repo, &head.to_string(), deployed).ok())
+            .flatten();
+        match (behind, ahead) {
+            (Some(behind), _) => format!("{behind} commits behind HEAD"),
+            (None, Some(ahead)) => format!("{ahead} commits ahead of HEAD"),
+            (None, None) => "not in the history of HEAD, try fetching".to_owned(),
+        }
+    };
+
+    let changes = match (deployed.dirty, local.provenance.dirty) {
+        (false, false) => "no uncommitted changes on either side",
+        (false, true) => "the work tree has changes that are not deployed",
+        (true, _) if deployed.diff_hash == local.provenance.diff_hash => {
+            "deployed with the same uncommitted changes as the work tree"
+        }
+        (true, _) => "deployed with uncommitted changes that differ from the work tree",
+    };
+
+    format!("{commits}, {changes}")
+}
+
+fn short_sha(sha: Option<&str>) -> &str {
+    sha.map(|sha| &sha[..sha.len().min(10)]).unwrap_or("an unknown commit")
+}
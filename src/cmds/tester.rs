@@ -1,9 +1,23 @@
-use std::{path::PathBuf, process::Command};
+use std::{
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
 
+use cargo_metadata::Message;
 use clap::Parser;
 
 use crate::{
+    actions::{
+        connections::ConnectionManager,
+        deploy_descriptor::{self, Descriptor},
+        git_commit::Provenance,
+        remote_log,
+        report::{self, LibtestParser, Report},
+        ssh_client::{FileMeta, TransferOptions},
+    },
     errors::TestSubcommandError,
+    output,
     project_config::{self, ProjectConfig},
 };
 
@@ -18,12 +32,19 @@ const USAGE: &str = "Usage Options";
 const TEST_OPTIONS: &str = "Test Options";
 const DISPLAY_OPTIONS: &str = "Display Options";
 
+/// Where test binaries are uploaded to, relative to the descriptor's deploy path.
+const TEST_DIR: &str = "tests";
+
 #[derive(Parser)]
 pub struct Test {
     //# USAGE
     #[arg(long, help_heading = USAGE)]
     pub no_dev: bool,
 
+    /// Build the tests for the robot and run them on it, args after `--` go to the test binaries
+    #[arg(long, conflicts_with = "message_format", help_heading = USAGE)]
+    pub real: bool,
+
     //# TEST OPTIONS
     #[arg(long, help_heading = TEST_OPTIONS)]
     pub no_run: bool,
@@ -116,20 +137,30 @@ pub struct Test {
     pub offline: bool,
 
     //# DISPLAY OPTIONS
-    #[arg(short, long, help_heading = DISPLAY_OPTIONS)]
+    /// Set from the global `-v`
+    #[arg(skip)]
     pub verbose: bool,
 
-    #[arg(short, long, help_heading = DISPLAY_OPTIONS)]
+    /// Set from the global `-q`
+    #[arg(skip)]
     pub quiet: bool,
 
+    /// Set from the global `--json`
+    #[arg(skip)]
+    pub json: bool,
+
     // Control when colored output is used
     #[arg(long, value_name = "WHEN", help_heading = DISPLAY_OPTIONS)]
     pub color: Option<String>,
 
     // The output format for diagnostic messages
-    #[arg(long, value_name = "FORMAT", help_heading = DISPLAY_OPTIONS)]
+    #[arg(long, value_name = "FORMAT", conflicts_with = "json", help_heading = DISPLAY_OPTIONS)]
     pub message_format: Option<String>,
 
+    /// Write a junit or json report of the diagnostics and test results to PATH, doc tests aren't run
+    #[arg(long, num_args = 2, value_names = ["FORMAT", "PATH"], conflicts_with = "message_format", help_heading = DISPLAY_OPTIONS)]
+    pub report: Option<Vec<String>>,
+
     //# MISC OPTIONS
     /// Number of parallel jobs, defaults to # of CPUs
     #[arg(short, long, value_name = "N", help_heading = MISC_OPTIONS)]
@@ -138,9 +169,12 @@ pub struct Test {
     #[arg(long, help_heading = MISC_OPTIONS)]
     pub future_incompat_report: bool,
 
-    //capture trailing args
-    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-    pub trailing: Vec<String>,
+    /// Only run the tests whose names contain TESTNAME
+    pub testname: Option<String>,
+
+    /// Args for the test binaries
+    #[arg(last = true, value_name = "ARGS")]
+    pub args: Vec<String>,
 }
 
 fn cmd_config(cmd: &mut Command, cfg: &str) {
@@ -148,29 +182,101 @@ fn cmd_config(cmd: &mut Command, cfg: &str) {
     cmd.arg(format!("build.rustflags = [\"--cfg\", \"{}\"]", cfg));
 }
 
+/// A test binary built by cargo.
+struct TestBinary {
+    /// The name of the test target.
+    name: String,
+    path: PathBuf,
+    /// The directory of the package's manifest, which cargo runs tests in.
+    package_dir: PathBuf,
+}
+
+/// How cargo-frc runs the test binaries when it runs them itself.
+struct RunOptions<'a> {
+    /// The test name filter, passed to every test binary first like cargo does.
+    filter: Option<&'a str>,
+    /// The args after `--`, passed to every test binary.
+    args: &'a [String],
+    no_run: bool,
+    no_fail_fast: bool,
+    /// Whether stdout is reserved for `--json`, the test output then goes to stderr.
+    json: bool,
+}
+
+impl RunOptions<'_> {
+    fn binary_args(&self) -> impl Iterator<Item = &str> {
+        self.filter
+            .into_iter()
+            .chain(self.args.iter().map(String::as_str))
+    }
+
+    fn print(&self, line: &str) {
+        if self.json {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
 pub fn cargo_test(test: Test, config: ProjectConfig) -> Result<(), TestSubcommandError> {
+    let descriptor = if test.real {
+        Some(deploy_descriptor::builtin_descriptor("rio").expect("the rio descriptor is builtin")?)
+    } else {
+        None
+    };
+    let report = test
+        .report
+        .as_deref()
+        .map(report::parse_option)
+        .transpose()
+        .map_err(TestSubcommandError::InvalidReport)?;
+    // the binaries are run by cargo-frc itself when it needs their output or runs them remotely
+    let run_binaries = test.real || report.is_some() || test.json;
+
     let mut cmd = Command::new(std::env::var("CARGO").unwrap_or("cargo".into()));
     cmd.arg("test");
 
     cmd.env("FRC_TEAM_NUMBER", config.team.0.to_string());
+    Provenance::current_or_unknown().apply_env(&mut cmd);
 
-    cmd_config(&mut cmd, project_config::DEFAULT_CFG);
-
+    let mut cfgs = vec![project_config::DEFAULT_CFG];
     if !test.no_dev {
-        cmd_config(&mut cmd, project_config::DEV_CFG);
-        cmd.arg("--target-dir");
-        cmd.arg(config.target_dirs.sim_dev);
+        cfgs.push(project_config::DEV_CFG);
+    }
+    if test.real {
+        cfgs.push(project_config::RUNTIME_REAL_CFG);
     } else {
-        cmd.arg("--target-dir");
-        cmd.arg(config.target_dirs.sim);
+        cfgs.push(project_config::RUNTIME_SIM_CFG);
+    }
+    for cfg in &cfgs {
+        cmd_config(&mut cmd, cfg);
     }
 
-    cmd_config(&mut cmd, project_config::RUNTIME_SIM_CFG);
+    let target_dir = match (test.real, test.no_dev) {
+        (false, false) => &config.target_dirs.sim_dev,
+        (false, true) => &config.target_dirs.sim,
+        (true, false) => &config.target_dirs.real_dev,
+        (true, true) => &config.target_dirs.real,
+    };
+    cmd.arg("--target-dir");
+    cmd.arg(target_dir);
 
-    if test.no_run {
+    if test.no_run || run_binaries {
         cmd.arg("--no-run");
     }
 
+    if run_binaries {
+        cmd.arg("--message-format=json");
+    }
+
+    if let Some(descriptor) = &descriptor {
+        if test.target.is_none() {
+            cmd.arg("--target");
+            cmd.arg(&descriptor.rust_target);
+        }
+    }
+
     if test.no_fail_fast {
         cmd.arg("--no-fail-fast");
     }
@@ -330,13 +436,44 @@ pub fn cargo_test(test: Test, config: ProjectConfig) -> Result<(), TestSubcomman
         cmd.arg("--future-incompat-report");
     }
 
-    cmd.args(test.trailing);
+    if run_binaries {
+        let runtime = if test.real { "real" } else { "sim" };
+        let mut results = Report::new("test", &cfgs, runtime, !test.no_dev);
+        let options = RunOptions {
+            filter: test.testname.as_deref(),
+            args: &test.args,
+            no_run: test.no_run,
+            no_fail_fast: test.no_fail_fast,
+            json: test.json,
+        };
+        let result = build_and_run(cmd, &config, descriptor.as_ref(), &options, &mut results);
+        results.success = result.is_ok();
+        if let Some((format, path)) = report {
+            results
+                .write(&path, format)
+                .map_err(|source| TestSubcommandError::WriteReport {
+                    path: path.clone(),
+                    source,
+                })?;
+            tracing::info!("Wrote the test report to {}", path.display());
+        }
+        if test.json {
+            output::print_json(&results);
+        }
+        return result;
+    }
+
+    cmd.args(test.testname);
+    if !test.args.is_empty() {
+        cmd.arg("--");
+        cmd.args(test.args);
+    }
 
     let exit_status = cmd
         .spawn()
-        .map_err(|_| TestSubcommandError::FailedToSpawnCargoTest)?
+        .map_err(TestSubcommandError::FailedToSpawnCargoTest)?
         .wait()
-        .map_err(|_| TestSubcommandError::FailedToWaitForCargoTest)?;
+        .map_err(TestSubcommandError::FailedToWaitForCargoTest)?;
 
     if exit_status.success() {
         Ok(())
@@ -346,3 +483,253 @@ pub fn cargo_test(test: Test, config: ProjectConfig) -> Result<(), TestSubcomman
         })
     }
 }
+
+/// Builds the tests, then runs them locally or on the robot, recording the results in `report`.
+fn build_and_run(
+    cmd: Command,
+    config: &ProjectConfig,
+    descriptor: Option<&Descriptor>,
+    options: &RunOptions<'_>,
+    report: &mut Report,
+) -> Result<(), TestSubcommandError> {
+    let binaries = build_tests(cmd, report)?;
+    match descriptor {
+        Some(descriptor) => {
+            let runtime = tokio::runtime::Runtime::new().map_err(TestSubcommandError::Runtime)?;
+            runtime.block_on(run_on_robot(config, descriptor, binaries, options, report))
+        }
+        None if options.no_run => Ok(()),
+        None => {
+            tracing::warn!(
+                "Doc tests aren't run with `--report` or `--json`, run `frc test` without them for the doc tests"
+            );
+            run_locally(binaries, options, report)
+        }
+    }
+}
+
+/// Runs the `--no-run` build and returns the test binaries cargo reports.
+///
+/// Compiler diagnostics are printed as cargo would and recorded in `report`.
+fn build_tests(
+    mut cmd: Command,
+    report: &mut Report,
+) -> Result<Vec<TestBinary>, TestSubcommandError> {
+    tracing::debug!("{:?}", cmd);
+
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(TestSubcommandError::FailedToSpawnCargoTest)?;
+
+    let mut binaries = Vec::new();
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    for message in Message::parse_stream(stdout).flatten() {
+        match message {
+            Message::CompilerArtifact(artifact) => {
+                if let Some(executable) = artifact.executable.filter(|_| artifact.profile.test) {
+                    let package_dir = artifact
+                        .manifest_path
+                        .parent()
+                        .map(|dir| dir.to_path_buf().into_std_path_buf())
+                        .unwrap_or_default();
+                    binaries.push(TestBinary {
+                        name: artifact.target.name,
+                        path: executable.into_std_path_buf(),
+                        package_dir,
+                    });
+                }
+            }
+            Message::CompilerMessage(message) => {
+                if let Some(rendered) = &message.message.rendered {
+                    eprint!("{}", rendered);
+                }
+                report.add_diagnostic(&message.message);
+            }
+            _ => {}
+        }
+    }
+
+    let exit_status = child
+        .wait()
+        .map_err(TestSubcommandError::FailedToWaitForCargoTest)?;
+    if !exit_status.success() {
+        return Err(TestSubcommandError::FailedCargoTest {
+            code: exit_status.code(),
+        });
+    }
+    Ok(binaries)
+}
+
+/// Runs the test binaries one after another like `cargo test` would.
+fn run_locally(
+    binaries: Vec<TestBinary>,
+    options: &RunOptions<'_>,
+    report: &mut Report,
+) -> Result<(), TestSubcommandError> {
+    let mut failed = Vec::new();
+    for binary in binaries {
+        options.print(&format!("     Running {}", binary.path.display()));
+        let mut child = Command::new(&binary.path)
+            .args(options.binary_args())
+            .current_dir(&binary.package_dir)
+            .env("CARGO_MANIFEST_DIR", &binary.package_dir)
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|source| TestSubcommandError::SpawnTestBinary {
+                path: binary.path.clone(),
+                source,
+            })?;
+
+        let mut parser = LibtestParser::new(&binary.name);
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        for line in stdout.lines().map_while(Result::ok) {
+            options.print(&line);
+            parser.line(&line);
+        }
+        report.suites.push(parser.finish());
+
+        let exit_status = child
+            .wait()
+            .map_err(|source| TestSubcommandError::WaitTestBinary {
+                path: binary.path.clone(),
+                source,
+            })?;
+        if !exit_status.success() {
+            failed.push(binary.name);
+            if !options.no_fail_fast {
+                break;
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(TestSubcommandError::FailedTests(failed))
+    }
+}
+
+/// Uploads the test binaries and, unless `--no-run` is set, runs them one after another on the robot.
+///
+/// The robot program is stopped while the tests have the hardware and started again afterwards.
+async fn run_on_robot(
+    config: &ProjectConfig,
+    descriptor: &Descriptor,
+    binaries: Vec<TestBinary>,
+    options: &RunOptions<'_>,
+    report: &mut Report,
+) -> Result<(), TestSubcommandError> {
+    let mut connections = ConnectionManager::new(config, descriptor);
+    let connection = connections
+        .target()
+        .await
+        .map_err(TestSubcommandError::Connect)?;
+
+    let test_dir = format!("{}/{}", descriptor.path.trim_end_matches('/'), TEST_DIR);
+    connection.create_dir_all(test_dir.as_str()).await?;
+
+    let mut uploaded = Vec::with_capacity(binaries.len());
+    for TestBinary {
+        name, path: binary, ..
+    } in binaries
+    {
+        let read_err = |source| TestSubcommandError::Read {
+            path: binary.clone(),
+            source,
+        };
+        let file_name = binary
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| name.clone());
+        let remote = format!("{}/{}", test_dir, file_name);
+        let options = TransferOptions {
+            preserve: Some(FileMeta::of_local(&binary).map_err(read_err)?),
+            ..TransferOptions::default()
+        };
+        let reader = tokio::fs::File::open(&binary).await.map_err(read_err)?;
+        tracing::info!("Uploading {} to {}", binary.display(), remote);
+        connection
+            .upload_from(remote.as_str(), reader, &options, &mut |_| {})
+            .await?;
+        uploaded.push((name, remote));
+    }
+    if options.no_run {
+        return Ok(());
+    }
+
+    let code = connection.call(&descriptor.stop_cmd).await?;
+    if code != 0 {
+        tracing::warn!("`{}` exited with {}", descriptor.stop_cmd, code);
+    }
+
+    // the robot program is started again however the tests went
+    let failed = run_uploaded(&mut connections, &test_dir, uploaded, options, report).await;
+    let restarted = match connections.target().await {
+        Ok(connection) => connection.call(&descriptor.start_cmd).await,
+        Err(err) => Err(err),
+    };
+    match restarted {
+        Ok(0) => {}
+        Ok(code) => tracing::warn!(
+            "`{}` exited with {}, restart the robot program",
+            descriptor.start_cmd,
+            code
+        ),
+        Err(err) => tracing::warn!(
+            "Could not run `{}`: {}, restart the robot program",
+            descriptor.start_cmd,
+            err
+        ),
+    }
+
+    let failed = failed?;
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(TestSubcommandError::FailedTests(failed))
+    }
+}
+
+/// Runs the uploaded test binaries from `test_dir`, returns the names of the failed ones.
+async fn run_uploaded(
+    connections: &mut ConnectionManager<'_>,
+    test_dir: &str,
+    uploaded: Vec<(String, String)>,
+    options: &RunOptions<'_>,
+    report: &mut Report,
+) -> Result<Vec<String>, TestSubcommandError> {
+    let args = options
+        .binary_args()
+        .map(|arg| format!(" '{}'", arg.replace('\'', r"'\''")))
+        .collect::<String>();
+    let mut failed = Vec::new();
+    for (name, remote) in uploaded {
+        options.print(&format!("     Running {} on the robot", name));
+        let connection = connections
+            .target()
+            .await
+            .map_err(TestSubcommandError::Connect)?;
+        let mut parser = LibtestParser::new(&name);
+        // only stdout is forwarded, libtest reports panics on stderr
+        let command = format!(
+            "cd {} && {}{} 2>&1",
+            remote_log::quote_path(test_dir),
+            remote_log::quote_path(&remote),
+            args
+        );
+        let code = remote_log::stream_lines(connection, &command, |line| {
+            options.print(line);
+            parser.line(line);
+        })
+        .await?;
+        report.suites.push(parser.finish());
+        if code != 0 {
+            failed.push(name);
+            if !options.no_fail_fast {
+                break;
+            }
+        }
+    }
+    Ok(failed)
+}
@@ -0,0 +1,160 @@
+use std::{collections::BTreeMap, net::Ipv4Addr, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ProjectConfigError;
+
+/// A standalone `frc.toml` next to Cargo.toml,
+/// the keys of `[package.metadata.frc]` plus an optional `package`.
+///
+/// Types are checked while reading the file, so mistakes point at their line in it.
+/// The values are checked against each other once every layer is merged.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FrcToml {
+    /// The robot package, when it can't be taken from the neighbouring Cargo.toml.
+    #[serde(skip_serializing)]
+    pub package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<TeamToml>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub robots: Option<Vec<RobotToml>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub robot: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_address: Option<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy_files: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_check: Option<RuntimeToml>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_tool: Option<ToolToml>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lints: Option<BTreeMap<String, LintToml>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitToml>,
+}
+
+/// The team number, as a number or a string of one.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TeamToml {
+    Number(u16),
+    String(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RobotToml {
+    pub name: String,
+    pub serials: Vec<String>,
+    #[serde(default)]
+    pub default: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<Ipv4Addr>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeToml {
+    Real,
+    Sim,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolToml {
+    Check,
+    Clippy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelToml {
+    Allow,
+    Warn,
+    Deny,
+    Forbid,
+}
+
+/// A lint level, or a table with a level and the only runtime it applies to.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LintToml {
+    Level(LevelToml),
+    Table {
+        level: LevelToml,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        runtime: Option<RuntimeToml>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct GitToml {
+    #[serde(default)]
+    pub competitions: Vec<CompetitionToml>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_clean: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompetitionToml {
+    /// `acronymn` is accepted for configs written before the spelling was fixed.
+    #[serde(alias = "acronymn")]
+    pub acronym: String,
+    /// Dates are `YYYY-MM-DD`, checked with the rest of the merged config.
+    pub start: String,
+    pub end: String,
+}
+
+impl FrcToml {
+    /// The keys of the file as a layer of the frc config.
+    pub fn frc_table(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("an frc.toml is serializable")
+    }
+}
+
+/// Reads and deserializes the `frc.toml` at `path`.
+pub fn read_frc_toml(path: &Path) -> Result<FrcToml, ProjectConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ProjectConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    toml::from_str(&contents).map_err(|source| ProjectConfigError::Toml {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frc_toml_table() {
+        let frc: FrcToml = toml::from_str(
+            r#"
+            package = "robot"
+            team = "1234"
+            robots = [{ name = "yin", serials = ["123456"], address = "172.22.11.2" }]
+            default-check = "sim"
+            lints = { unwrap = { level = "deny", runtime = "real" }, blocking-sleep = "forbid" }
+            git = { competitions = [{ acronymn = "flr", start = "2024-03-01", end = "2024-03-03" }] }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(frc.package.as_deref(), Some("robot"));
+        let table = frc.frc_table();
+        assert!(table.get("package").is_none());
+        assert_eq!(table["team"], "1234");
+        assert_eq!(table["robots"][0]["address"], "172.22.11.2");
+        assert_eq!(table["lints"]["unwrap"]["runtime"], "real");
+        assert_eq!(table["git"]["competitions"][0]["acronym"], "flr");
+
+        assert!(toml::from_str::<FrcToml>("team = 1234\ndeploy_dir = \"deploy\"").is_err());
+    }
+}
@@ -2,12 +2,54 @@ use std::path::PathBuf;
 
 use serde::{Serialize, Deserialize};
 
+use crate::errors::ProjectConfigError;
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct GlobalConfig {
     /// The default team number to use when creating new projects.
     pub team: Option<u16>,
     /// An array of paths to deploy descriptors or directories containing deploy descriptors.
+    #[serde(default)]
     pub deploy_descriptor_paths: Vec<PathBuf>,
     
-}
\ No newline at end of file
+}
+impl GlobalConfig {
+    /// The keys of the global config that act as defaults for the project's frc config.
+    pub fn frc_table(&self) -> serde_json::Value {
+        let mut table = serde_json::Map::new();
+        if let Some(team) = self.team {
+            table.insert("team".to_owned(), team.into());
+        }
+        serde_json::Value::Object(table)
+    }
+}
+
+/// The path of the global config,
+/// `$FRC_GLOBAL_CONFIG` or else `cargo-frc/config.toml` in the user's config directory.
+pub fn global_config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("FRC_GLOBAL_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(config_dir.join("cargo-frc").join("config.toml"))
+}
+
+/// Reads the global config, `None` if there is none.
+pub fn read_global_config() -> Result<Option<(PathBuf, GlobalConfig)>, ProjectConfigError> {
+    let Some(path) = global_config_path().filter(|path| path.is_file()) else {
+        return Ok(None);
+    };
+    let contents = std::fs::read_to_string(&path).map_err(|source| ProjectConfigError::Io {
+        path: path.clone(),
+        source,
+    })?;
+    let config = toml::from_str(&contents).map_err(|source| ProjectConfigError::Toml {
+        path: path.clone(),
+        source,
+    })?;
+    Ok(Some((path, config)))
+}
@@ -0,0 +1,173 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use clap::Args;
+
+/// Where the effective value of a config key came from.
+#[derive(Debug, Clone)]
+pub enum Origin {
+    Cli(&'static str),
+    Env(&'static str),
+    Project(PathBuf),
+    Global(PathBuf),
+    Default,
+}
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cli(flag) => write!(f, "command line `{flag}`"),
+            Self::Env(var) => write!(f, "environment variable `{var}`"),
+            Self::Project(path) => write!(f, "project {}", path.display()),
+            Self::Global(path) => write!(f, "global {}", path.display()),
+            Self::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// The config keys that can be overridden from the environment,
+/// as `(key, environment variable)`.
+pub const ENV_OVERRIDES: [(&str, &str); 8] = [
+    ("team", "FRC_TEAM_NUMBER"),
+    ("override-address", "FRC_ADDRESS"),
+    ("robot", "FRC_ROBOT"),
+    ("deploy-dir", "FRC_DEPLOY_DIR"),
+    ("default-check", "FRC_DEFAULT_CHECK"),
+    ("deploy-files", "FRC_DEPLOY_FILES"),
+    ("default-tool", "FRC_DEFAULT_TOOL"),
+    ("git.require-clean", "FRC_REQUIRE_CLEAN"),
+];
+
+/// Overrides for the project config that apply to every subcommand.
+#[derive(Debug, Default, Args)]
+pub struct ConfigOverrides {
+    /// Override the team number
+    #[arg(long, global = true, value_name = "NUMBER")]
+    pub team: Option<String>,
+
+    /// Override the address of the robot
+    #[arg(long, global = true, value_name = "IPV4")]
+    pub address: Option<String>,
+
+    /// Select the robot by name
+    #[arg(long, global = true, value_name = "NAME")]
+    pub robot: Option<String>,
+
+    /// Override the deploy directory
+    #[arg(long, global = true, value_name = "PATH")]
+    pub deploy_dir: Option<String>,
+
+    /// Override the runtime checked by default
+    #[arg(long, global = true, value_name = "real|sim")]
+    pub default_check: Option<String>,
+
+    /// Override the local directory uploaded into the deploy directory
+    #[arg(long, global = true, value_name = "PATH")]
+    pub deploy_files: Option<String>,
+
+    /// Override the tool `check` runs by default
+    #[arg(long, global = true, value_name = "check|clippy")]
+    pub default_tool: Option<String>,
+
+    /// Override whether uncommitted changes may be deployed outside a competition
+    #[arg(long, global = true, value_name = "true|false")]
+    pub require_clean: Option<String>,
+}
+impl ConfigOverrides {
+    fn flags(&self) -> [(&'static str, &'static str, Option<&String>); 8] {
+        [
+            ("team", "--team", self.team.as_ref()),
+            ("override-address", "--address", self.address.as_ref()),
+            ("robot", "--robot", self.robot.as_ref()),
+            ("deploy-dir", "--deploy-dir", self.deploy_dir.as_ref()),
+            ("default-check", "--default-check", self.default_check.as_ref()),
+            ("deploy-files", "--deploy-files", self.deploy_files.as_ref()),
+            ("default-tool", "--default-tool", self.default_tool.as_ref()),
+            ("git.require-clean", "--require-clean", self.require_clean.as_ref()),
+        ]
+    }
+}
+
+/// The frc config assembled from several sources,
+/// a key set by a later layer replaces the same key of an earlier one.
+///
+/// Tables are merged key by key, so a member's `[package.metadata.frc.git]` only replaces
+/// the keys it sets of the workspace's, arrays and other values are replaced as a whole.
+/// The origin of every key is recorded under its dotted path, like `git.require-clean`,
+/// a table's own path records the last layer that set any key in it.
+///
+/// The layers are pushed in order of increasing precedence:
+/// global config, project config, `FRC_*` environment variables, command line.
+#[derive(Debug, Default)]
+pub struct Layers {
+    merged: serde_json::Map<String, serde_json::Value>,
+    origins: BTreeMap<String, Origin>,
+}
+impl Layers {
+    pub fn push(&mut self, origin: Origin, table: &serde_json::Value) {
+        let Some(table) = table.as_object() else {
+            return;
+        };
+        merge(&mut self.merged, &mut self.origins, "", table, &origin);
+    }
+
+    /// Sets the value of a dotted key, like `git.require-clean`.
+    fn push_value(&mut self, origin: Origin, key: &str, value: &str) {
+        let table = key
+            .rsplit('.')
+            .fold(serde_json::Value::from(value), |value, key| {
+                serde_json::Value::Object([(key.to_owned(), value)].into_iter().collect())
+            });
+        self.push(origin, &table);
+    }
+
+    pub fn push_env(&mut self) {
+        for (key, var) in ENV_OVERRIDES {
+            if let Ok(value) = std::env::var(var) {
+                self.push_value(Origin::Env(var), key, &value);
+            }
+        }
+    }
+
+    pub fn push_cli(&mut self, overrides: &ConfigOverrides) {
+        for (key, flag, value) in overrides.flags() {
+            if let Some(value) = value {
+                self.push_value(Origin::Cli(flag), key, value);
+            }
+        }
+    }
+
+    pub fn finish(self) -> (serde_json::Value, BTreeMap<String, Origin>) {
+        (serde_json::Value::Object(self.merged), self.origins)
+    }
+}
+
+fn merge(
+    merged: &mut serde_json::Map<String, serde_json::Value>,
+    origins: &mut BTreeMap<String, Origin>,
+    prefix: &str,
+    table: &serde_json::Map<String, serde_json::Value>,
+    origin: &Origin,
+) {
+    for (key, value) in table {
+        let path = match prefix {
+            "" => key.clone(),
+            _ => format!("{prefix}.{key}"),
+        };
+        let existing = merged.entry(key.clone()).or_insert(serde_json::Value::Null);
+        match value {
+            serde_json::Value::Object(value) => {
+                if !existing.is_object() {
+                    *existing = serde_json::Value::Object(serde_json::Map::new());
+                }
+                if let serde_json::Value::Object(existing) = existing {
+                    merge(existing, origins, &path, value, origin);
+                }
+            }
+            value => {
+                let nested = format!("{path}.");
+                origins.retain(|key, _| !key.starts_with(&nested));
+                *existing = value.clone();
+            }
+        }
+        origins.insert(path, origin.clone());
+    }
+}
@@ -1,2 +1,4 @@
 pub mod project_config;
-pub mod global_config;
\ No newline at end of file
+pub mod global_config;
+pub mod layers;
+pub mod frc_toml;
\ No newline at end of file
@@ -1,7 +1,18 @@
-use std::{collections::HashSet, net::Ipv4Addr, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDate;
 
 use crate::errors::ProjectConfigError;
 
+use super::{
+    frc_toml, global_config,
+    layers::{ConfigOverrides, Layers, Origin},
+};
+
 pub const RUNTIME_REAL_CFG: &str = "frc_real";
 pub const RUNTIME_SIM_CFG: &str = "frc_sim";
 pub const DEV_CFG: &str = "frc_dev";
@@ -14,6 +25,10 @@ pub struct TeamNumber(pub u16);
 pub struct Robot {
     pub name: String,
     pub serials: HashSet<String>,
+    /// Whether this robot is the one used when no robot is selected.
+    pub default: bool,
+    /// Where the robot is reached if not at the team's address, e.g. over USB.
+    pub address: Option<Ipv4Addr>,
 }
 
 #[derive(Debug)]
@@ -34,36 +49,397 @@ impl TargetDirs {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Runtimes {
     Real,
     Sim,
 }
+impl Runtimes {
+    /// The name used for the runtime in `default-check` and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Real => "real",
+            Self::Sim => "sim",
+        }
+    }
+}
+
+/// The cargo subcommand `cargo frc check` runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CheckTool {
+    Check,
+    Clippy,
+}
+impl CheckTool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Check => "check",
+            Self::Clippy => "clippy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+    Forbid,
+}
+impl LintLevel {
+    /// The rustc flag setting a lint to this level.
+    pub fn flag(self) -> &'static str {
+        match self {
+            Self::Allow => "-A",
+            Self::Warn => "-W",
+            Self::Deny => "-D",
+            Self::Forbid => "-F",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Warn => "warn",
+            Self::Deny => "deny",
+            Self::Forbid => "forbid",
+        }
+    }
+}
+
+/// An entry of the `lints` table.
+#[derive(Debug, Clone)]
+pub struct Lint {
+    /// A rustc or clippy lint, or one of the FRC lints like `unwrap` or `blocking-sleep`.
+    pub name: String,
+    pub level: LintLevel,
+    /// The only runtime the level applies to, every runtime if `None`.
+    pub runtime: Option<Runtimes>,
+}
+
+/// An event during which every deploy is committed to `comp/<acronym>`.
+#[derive(Debug)]
+pub struct Competition {
+    pub acronym: String,
+    /// The first day of the event.
+    pub start: NaiveDate,
+    /// The last day of the event, inclusive.
+    pub end: NaiveDate,
+}
+
+/// The `git` table of the frc config.
+#[derive(Debug, Default)]
+pub struct GitConfig {
+    pub competitions: Vec<Competition>,
+    /// Refuse to deploy uncommitted changes, except during a competition.
+    pub require_clean: bool,
+}
+impl GitConfig {
+    /// Sorts the competitions by date, rejecting inverted or overlapping date ranges.
+    pub fn new(mut competitions: Vec<Competition>) -> Result<Self, ProjectConfigError> {
+        if let Some(competition) = competitions.iter().find(|c| c.end < c.start) {
+            return Err(ProjectConfigError::InvertedCompetition(
+                competition.acronym.clone(),
+            ));
+        }
+        competitions.sort_by_key(|competition| competition.start);
+        if let Some(pair) = competitions
+            .windows(2)
+            .find(|pair| pair[1].start <= pair[0].end)
+        {
+            return Err(ProjectConfigError::OverlappingCompetitions(
+                pair[0].acronym.clone(),
+                pair[1].acronym.clone(),
+            ));
+        }
+        Ok(Self {
+            competitions,
+            require_clean: false,
+        })
+    }
+
+    /// The competition taking place on `date`, if any.
+    pub fn active(&self, date: NaiveDate) -> Option<&Competition> {
+        self.competitions
+            .iter()
+            .find(|competition| competition.start <= date && date <= competition.end)
+    }
+
+    /// The first competition starting after `date`, if any.
+    pub fn next(&self, date: NaiveDate) -> Option<&Competition> {
+        self.competitions
+            .iter()
+            .find(|competition| competition.start > date)
+    }
+}
 
 #[derive(Debug)]
 pub struct ProjectConfig {
+    /// The name of the robot package.
+    pub package: String,
     pub team: TeamNumber,
     pub robots: Vec<Robot>,
+    /// The name of the selected robot, from `robot` or else the robot marked `default`.
+    pub robot: Option<String>,
     pub address: Option<Ipv4Addr>,
+    /// Where the extra files go on the robot, relative to the deploy user's home directory.
     pub deploy_dir: Option<String>,
+    /// The local directory uploaded into `deploy_dir`, resolved against the config file that set it.
+    pub deploy_files: Option<PathBuf>,
     pub default_check: Runtimes,
+    /// What `cargo frc check` runs without `--tool`, clippy unless `default-tool` says otherwise.
+    pub default_tool: CheckTool,
+    pub lints: Vec<Lint>,
     pub target_dirs: TargetDirs,
+    pub git: GitConfig,
+    /// Where each config key that was set came from.
+    pub origins: BTreeMap<String, Origin>,
+}
+impl ProjectConfig {
+    /// The address of the selected robot as in [`robot_address`](Self::robot_address),
+    /// `override-address` or else the team's when no configured robot is selected.
+    pub fn target_address(&self) -> Ipv4Addr {
+        match self
+            .robots
+            .iter()
+            .find(|robot| Some(&robot.name) == self.robot.as_ref())
+        {
+            Some(robot) => self.robot_address(robot),
+            None => self.address.unwrap_or_else(|| self.team_address()),
+        }
+    }
+
+    /// The address of a robot, `override-address`, its own `address`,
+    /// or else the team's `10.TE.AM.2`.
+    pub fn robot_address(&self, robot: &Robot) -> Ipv4Addr {
+        self.address
+            .or(robot.address)
+            .unwrap_or_else(|| self.team_address())
+    }
+
+    /// The configured robot with the given serial number.
+    pub fn robot_with_serial(&self, serial: &str) -> Option<&Robot> {
+        self.robots.iter().find(|robot| robot.serials.contains(serial))
+    }
+
+    fn team_address(&self) -> Ipv4Addr {
+        let team = self.team.0;
+        Ipv4Addr::new(10, (team / 100) as u8, (team % 100) as u8, 2)
+    }
+
+    pub fn origin(&self, key: &str) -> Origin {
+        self.origins.get(key).cloned().unwrap_or(Origin::Default)
+    }
+}
+
+/// Finds the nearest `Cargo.toml` in the current directory or any of its parents.
+pub fn find_manifest() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest| manifest.is_file())
+}
+
+pub const FRC_TOML: &str = "frc.toml";
+const DEPLOY_FILES: &str = "deploy-files";
+
+/// Finds the `frc.toml` next to the manifest, if there is one.
+pub fn find_frc_toml(manifest_path: Option<&Path>) -> Option<PathBuf> {
+    let manifest = match manifest_path {
+        Some(manifest_path) => manifest_path.to_path_buf(),
+        None => find_manifest()?,
+    };
+    Some(manifest.parent()?.join(FRC_TOML)).filter(|frc_toml| frc_toml.is_file())
+}
+
+/// Reads the frc config of the robot package.
+///
+/// The config is assembled from several layers, see [`Layers`],
+/// the global config, the project config, `FRC_*` environment variables and `overrides`.
+///
+/// If there is an `frc.toml` next to the manifest it is the project config and cargo is never invoked,
+/// it has the same keys as `[package.metadata.frc]` plus an optional `package`
+/// naming the robot package when it can't be taken from the neighbouring Cargo.toml.
+///
+/// Otherwise the project config is the `[workspace.metadata.frc]` table of the workspace, if any,
+/// with the keys of the package's own `[package.metadata.frc]` table taking precedence.
+/// `package` selects the robot package by name, when not given the root package is used,
+/// or in a virtual workspace the only member with an frc table.
+pub fn read_config2(
+    overrides: &ConfigOverrides,
+    manifest_path: Option<&Path>,
+    package: Option<&str>,
+) -> Result<ProjectConfig, ProjectConfigError> {
+    let _span = tracing::info_span!("config").entered();
+    let mut layers = Layers::default();
+    if let Some((path, global)) = global_config::read_global_config()? {
+        layers.push(Origin::Global(path), &global.frc_table());
+    }
+
+    let (package, target_dirs) = match find_frc_toml(manifest_path) {
+        Some(frc_toml) => read_frc_toml(&mut layers, &frc_toml, package)?,
+        None => read_cargo_metadata(&mut layers, manifest_path, package)?,
+    };
+
+    layers.push_env();
+    layers.push_cli(overrides);
+
+    let (frc_cfg, origins) = layers.finish();
+    let mut config = parse_frc_table(&frc_cfg, package, target_dirs).map_err(|err| {
+        // only point at the config file if the bad value actually came from it
+        let origin = err.attribute().and_then(|attr| {
+            let mut key = attr.split('[').next().unwrap_or(attr);
+            loop {
+                match origins.get(key) {
+                    Some(origin) => return Some(origin),
+                    None => key = &key[..key.rfind('.')?],
+                }
+            }
+        });
+        match origin {
+            Some(origin @ (Origin::Cli(_) | Origin::Env(_))) => {
+                ProjectConfigError::InvalidOverride(origin.to_string(), Box::new(err))
+            }
+            _ => err,
+        }
+    })?;
+    if let Some(dir) = config.deploy_files.take() {
+        config.deploy_files = Some(match origins.get(DEPLOY_FILES) {
+            Some(Origin::Project(path) | Origin::Global(path)) => {
+                path.parent().unwrap_or(Path::new(".")).join(dir)
+            }
+            _ => dir,
+        });
+    }
+    config.origins = origins;
+    Ok(config)
 }
 
-pub fn read_config2() -> Result<ProjectConfig, ProjectConfigError> {
-    let raw = cargo_metadata::MetadataCommand::new().exec()?;
+fn read_cargo_metadata(
+    layers: &mut Layers,
+    manifest_path: Option<&Path>,
+    package: Option<&str>,
+) -> Result<(String, TargetDirs), ProjectConfigError> {
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.no_deps();
+    if let Some(manifest_path) = manifest_path {
+        cmd.manifest_path(manifest_path);
+    }
+    let raw = cmd.exec()?;
 
     let target_dirs = TargetDirs::from_rel_target_dir(raw.target_directory.clone().into());
 
-    let package = raw
-        .root_package()
-        .ok_or(ProjectConfigError::MissingRootPackage)?;
+    let package = select_package(&raw, package)?;
 
-    let frc_cfg = package
-        .metadata
-        .get("frc")
-        .ok_or(ProjectConfigError::MissingFrcMetadata)?;
+    let workspace_frc = raw.workspace_metadata.get("frc");
+    let package_frc = package.metadata.get("frc");
+    if workspace_frc.is_none() && package_frc.is_none() {
+        return Err(ProjectConfigError::MissingFrcMetadata);
+    }
+    if let Some(workspace_frc) = workspace_frc {
+        let manifest = raw.workspace_root.join("Cargo.toml");
+        layers.push(Origin::Project(manifest.into()), workspace_frc);
+    }
+    if let Some(package_frc) = package_frc {
+        let manifest = package.manifest_path.clone();
+        layers.push(Origin::Project(manifest.into()), package_frc);
+    }
 
+    Ok((package.name.clone(), target_dirs))
+}
+
+fn read_toml(path: &Path) -> Result<serde_json::Value, ProjectConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ProjectConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    toml::from_str(&contents).map_err(|source| ProjectConfigError::Toml {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn read_frc_toml(
+    layers: &mut Layers,
+    frc_toml: &Path,
+    package: Option<&str>,
+) -> Result<(String, TargetDirs), ProjectConfigError> {
+    tracing::debug!("reading config from {}", frc_toml.display());
+    let frc_cfg = frc_toml::read_frc_toml(frc_toml)?;
+    let dir = frc_toml.parent().unwrap_or(Path::new("."));
+
+    let package = match package.or(frc_cfg.package.as_deref()) {
+        Some(package) => package.to_owned(),
+        None => read_toml(&dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|manifest| {
+                manifest
+                    .get("package")?
+                    .get("name")?
+                    .as_str()
+                    .map(|s| s.to_owned())
+            })
+            .ok_or(ProjectConfigError::MissingAttribute("package"))?,
+    };
+
+    let target_dirs = TargetDirs::from_rel_target_dir(find_target_dir(dir));
+
+    layers.push(Origin::Project(frc_toml.to_path_buf()), &frc_cfg.frc_table());
+
+    Ok((package, target_dirs))
+}
+
+/// Finds cargo's target directory without asking cargo,
+/// `$CARGO_TARGET_DIR` or the `target` directory of the outermost workspace.
+fn find_target_dir(dir: &Path) -> PathBuf {
+    if let Some(target_dir) = std::env::var_os("CARGO_TARGET_DIR") {
+        return PathBuf::from(target_dir);
+    }
+    dir.ancestors()
+        .filter(|dir| {
+            read_toml(&dir.join("Cargo.toml"))
+                .map(|manifest| manifest.get("workspace").is_some())
+                .unwrap_or(false)
+        })
+        .last()
+        .unwrap_or(dir)
+        .join("target")
+}
+
+fn select_package<'a>(
+    raw: &'a cargo_metadata::Metadata,
+    name: Option<&str>,
+) -> Result<&'a cargo_metadata::Package, ProjectConfigError> {
+    let members = raw.workspace_packages();
+    if let Some(name) = name {
+        return members
+            .into_iter()
+            .find(|package| package.name == name)
+            .ok_or_else(|| ProjectConfigError::UnknownPackage(name.to_owned()));
+    }
+    if let Some(root) = raw.root_package() {
+        return Ok(root);
+    }
+    if let [only] = members.as_slice() {
+        return Ok(only);
+    }
+    let robots = members
+        .iter()
+        .filter(|package| package.metadata.get("frc").is_some())
+        .collect::<Vec<_>>();
+    match robots.as_slice() {
+        [robot] => Ok(robot),
+        _ => Err(ProjectConfigError::AmbiguousPackage(
+            members.iter().map(|package| package.name.clone()).collect(),
+        )),
+    }
+}
+
+fn parse_frc_table(
+    frc_cfg: &serde_json::Value,
+    package: String,
+    target_dirs: TargetDirs,
+) -> Result<ProjectConfig, ProjectConfigError> {
     let team = TeamNumber({
         const TEAM_HINT: &str =
             "a number or a string that can be parsed as a number thats less than 65536";
@@ -88,9 +464,13 @@ pub fn read_config2() -> Result<ProjectConfig, ProjectConfigError> {
         const ROBOTS_HINT: &str = "an array of objects with a name and serials attribute";
         const ROBOTS_NAME_HINT: &str = "a string";
         const ROBOTS_SERIALS_HINT: &str = "an array of strings";
+        const ROBOTS_DEFAULT_HINT: &str = "a boolean";
+        const ROBOTS_ADDRESS_HINT: &str = "a string that can be parsed as an ipv4 address";
         const ROBOTS: &str = "robots";
         const ROBOTS_NAME: &str = "robots[i].name";
         const ROBOTS_SERIALS: &str = "robots[i].serials";
+        const ROBOTS_DEFAULT: &str = "robots[i].default";
+        const ROBOTS_ADDRESS: &str = "robots[i].address";
         frc_cfg
             .get("robots")
             .ok_or(ProjectConfigError::MissingAttribute(ROBOTS))?
@@ -128,11 +508,56 @@ pub fn read_config2() -> Result<ProjectConfig, ProjectConfigError> {
                     .collect::<Result<Vec<String>, ProjectConfigError>>()?
                     .into_iter()
                     .collect::<HashSet<String>>();
-                Ok(Robot { name, serials })
+                let default = robot
+                    .get("default")
+                    .map(|default| {
+                        default.as_bool().ok_or(ProjectConfigError::ParseAttribute(
+                            ROBOTS_DEFAULT,
+                            ROBOTS_DEFAULT_HINT,
+                        ))
+                    })
+                    .transpose()?
+                    .unwrap_or(false);
+                let address = robot
+                    .get("address")
+                    .map(|address| {
+                        address
+                            .as_str()
+                            .and_then(|s| s.parse::<Ipv4Addr>().ok())
+                            .ok_or(ProjectConfigError::ParseAttribute(
+                                ROBOTS_ADDRESS,
+                                ROBOTS_ADDRESS_HINT,
+                            ))
+                    })
+                    .transpose()?;
+                Ok(Robot {
+                    name,
+                    serials,
+                    default,
+                    address,
+                })
             })
             .collect::<Result<Vec<Robot>, ProjectConfigError>>()?
     };
 
+    let robot = {
+        const ROBOT_HINT: &str = "the name of one of the robots";
+        const ROBOT: &str = "robot";
+        match frc_cfg.get(ROBOT) {
+            Some(name) => {
+                let name = name
+                    .as_str()
+                    .filter(|name| robots.iter().any(|robot| robot.name == *name))
+                    .ok_or(ProjectConfigError::ParseAttribute(ROBOT, ROBOT_HINT))?;
+                Some(name.to_owned())
+            }
+            None => robots
+                .iter()
+                .find(|robot| robot.default)
+                .map(|robot| robot.name.clone()),
+        }
+    };
+
     let address = {
         const OVERRIDE_ADDRESS_HINT: &str = "a string that can be parsed as an ipv4 address";
         const OVERRIDE_ADDRESS: &str = "override-address";
@@ -172,6 +597,22 @@ pub fn read_config2() -> Result<ProjectConfig, ProjectConfigError> {
             .transpose()?
     };
 
+    let deploy_files = {
+        const DEPLOY_FILES_HINT: &str =
+            "a string that is a local path, relative to the config file it is set in";
+        frc_cfg
+            .get(DEPLOY_FILES)
+            .map(|dir| {
+                dir.as_str()
+                    .ok_or(ProjectConfigError::ParseAttribute(
+                        DEPLOY_FILES,
+                        DEPLOY_FILES_HINT,
+                    ))
+                    .map(PathBuf::from)
+            })
+            .transpose()?
+    };
+
     let default_check = {
         const DEFAULT_CHECK_HINT: &str = "a string that is either \"real\" or \"sim\"";
         const DEFAULT_CHECK: &str = "default-check";
@@ -194,12 +635,318 @@ pub fn read_config2() -> Result<ProjectConfig, ProjectConfigError> {
         }
     };
 
+    let default_tool = {
+        const DEFAULT_TOOL_HINT: &str = "a string that is either \"check\" or \"clippy\"";
+        const DEFAULT_TOOL: &str = "default-tool";
+        match frc_cfg.get(DEFAULT_TOOL) {
+            Some(tool) => match tool.as_str() {
+                Some("check") => CheckTool::Check,
+                Some("clippy") => CheckTool::Clippy,
+                _ => {
+                    return Err(ProjectConfigError::ParseAttribute(
+                        DEFAULT_TOOL,
+                        DEFAULT_TOOL_HINT,
+                    ))
+                }
+            },
+            None => CheckTool::Clippy,
+        }
+    };
+
+    let lints = {
+        const LINTS_HINT: &str = "a table of lint names to a level or to a table with a level and runtime";
+        const LEVEL_HINT: &str = "one of \"allow\", \"warn\", \"deny\" or \"forbid\"";
+        const RUNTIME_HINT: &str = "a string that is either \"real\" or \"sim\"";
+        const LINTS: &str = "lints";
+        const LEVEL: &str = "lints.<lint>.level";
+        const RUNTIME: &str = "lints.<lint>.runtime";
+        let parse_level = |level: &serde_json::Value| match level.as_str() {
+            Some("allow") => Ok(LintLevel::Allow),
+            Some("warn") => Ok(LintLevel::Warn),
+            Some("deny") => Ok(LintLevel::Deny),
+            Some("forbid") => Ok(LintLevel::Forbid),
+            _ => Err(ProjectConfigError::ParseAttribute(LEVEL, LEVEL_HINT)),
+        };
+        match frc_cfg.get(LINTS) {
+            Some(lints) => lints
+                .as_object()
+                .ok_or(ProjectConfigError::ParseAttribute(LINTS, LINTS_HINT))?
+                .iter()
+                .map(|(name, lint)| {
+                    let (level, runtime) = match lint {
+                        serde_json::Value::Object(table) => (
+                            parse_level(
+                                table
+                                    .get("level")
+                                    .ok_or(ProjectConfigError::MissingAttribute(LEVEL))?,
+                            )?,
+                            match table.get("runtime").map(|runtime| runtime.as_str()) {
+                                None => None,
+                                Some(Some("real")) => Some(Runtimes::Real),
+                                Some(Some("sim")) => Some(Runtimes::Sim),
+                                Some(_) => {
+                                    return Err(ProjectConfigError::ParseAttribute(
+                                        RUNTIME,
+                                        RUNTIME_HINT,
+                                    ))
+                                }
+                            },
+                        ),
+                        level => (parse_level(level)?, None),
+                    };
+                    Ok(Lint {
+                        name: name.clone(),
+                        level,
+                        runtime,
+                    })
+                })
+                .collect::<Result<Vec<Lint>, ProjectConfigError>>()?,
+            None => Vec::new(),
+        }
+    };
+
+    let git = {
+        const COMPETITIONS_HINT: &str = "an array of objects with an acronym, start and end attribute";
+        const ACRONYM_HINT: &str = "a string";
+        const DATE_HINT: &str = "a date formatted as YYYY-MM-DD";
+        const COMPETITIONS: &str = "git.competitions";
+        const ACRONYM: &str = "git.competitions[i].acronym";
+        const START: &str = "git.competitions[i].start";
+        const END: &str = "git.competitions[i].end";
+        let parse_date = |competition: &serde_json::Value, key: &'static str, name| {
+            competition
+                .get(key)
+                .ok_or(ProjectConfigError::MissingAttribute(name))?
+                .as_str()
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+                .ok_or(ProjectConfigError::ParseAttribute(name, DATE_HINT))
+        };
+        let competitions = match frc_cfg.get("git").and_then(|git| git.get("competitions")) {
+            Some(competitions) => competitions
+                .as_array()
+                .ok_or(ProjectConfigError::ParseAttribute(COMPETITIONS, COMPETITIONS_HINT))?
+                .iter()
+                .map(|competition| {
+                    // `acronymn` is accepted for configs written before the spelling was fixed
+                    let acronym = competition
+                        .get("acronym")
+                        .or_else(|| competition.get("acronymn"))
+                        .ok_or(ProjectConfigError::MissingAttribute(ACRONYM))?
+                        .as_str()
+                        .ok_or(ProjectConfigError::ParseAttribute(ACRONYM, ACRONYM_HINT))?
+                        .to_owned();
+                    Ok(Competition {
+                        acronym,
+                        start: parse_date(competition, "start", START)?,
+                        end: parse_date(competition, "end", END)?,
+                    })
+                })
+                .collect::<Result<Vec<Competition>, ProjectConfigError>>()?,
+            None => Vec::new(),
+        };
+        let require_clean = {
+            const REQUIRE_CLEAN_HINT: &str = "a boolean";
+            const REQUIRE_CLEAN: &str = "git.require-clean";
+            frc_cfg
+                .get("git")
+                .and_then(|git| git.get("require-clean"))
+                .map(|require_clean| match require_clean {
+                    serde_json::Value::Bool(b) => Ok(*b),
+                    // overrides from the environment or command line are strings
+                    serde_json::Value::String(s) => s.parse::<bool>().map_err(|_| {
+                        ProjectConfigError::ParseAttribute(REQUIRE_CLEAN, REQUIRE_CLEAN_HINT)
+                    }),
+                    _ => Err(ProjectConfigError::ParseAttribute(
+                        REQUIRE_CLEAN,
+                        REQUIRE_CLEAN_HINT,
+                    )),
+                })
+                .transpose()?
+                .unwrap_or(false)
+        };
+        GitConfig {
+            require_clean,
+            ..GitConfig::new(competitions)?
+        }
+    };
+
     Ok(ProjectConfig {
+        package,
         team,
         robots,
+        robot,
         address,
         deploy_dir,
+        deploy_files,
         default_check,
+        default_tool,
+        lints,
         target_dirs,
+        git,
+        origins: BTreeMap::new(),
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn competition(acronym: &str, start: &str, end: &str) -> Competition {
+        Competition {
+            acronym: acronym.to_owned(),
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_git_config_competitions() {
+        let git = GitConfig::new(vec![
+            competition("sfr", "2024-03-08", "2024-03-10"),
+            competition("flr", "2024-03-01", "2024-03-03"),
+        ])
+        .unwrap();
+        let date = "2024-03-03".parse().unwrap();
+        assert_eq!(git.active(date).map(|c| c.acronym.as_str()), Some("flr"));
+        assert_eq!(git.next(date).map(|c| c.acronym.as_str()), Some("sfr"));
+
+        assert!(matches!(
+            GitConfig::new(vec![competition("flr", "2024-03-03", "2024-03-01")]),
+            Err(ProjectConfigError::InvertedCompetition(_))
+        ));
+        assert!(matches!(
+            GitConfig::new(vec![
+                competition("flr", "2024-03-01", "2024-03-03"),
+                competition("sfr", "2024-03-03", "2024-03-05"),
+            ]),
+            Err(ProjectConfigError::OverlappingCompetitions(..))
+        ));
+    }
+
+    #[test]
+    fn test_parse_lints() {
+        let frc_cfg = serde_json::json!({
+            "team": 1234,
+            "robots": [],
+            "default-check": "sim",
+            "default-tool": "check",
+            "lints": {
+                "unwrap": { "level": "deny", "runtime": "real" },
+                "blocking-sleep": "forbid",
+            },
+        });
+        let dirs = TargetDirs::from_rel_target_dir(PathBuf::from("target"));
+        let config = parse_frc_table(&frc_cfg, "robot".to_owned(), dirs).unwrap();
+        assert_eq!(config.default_tool, CheckTool::Check);
+        let lints = config
+            .lints
+            .iter()
+            .map(|lint| (lint.name.as_str(), lint.level, lint.runtime))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lints,
+            vec![
+                ("blocking-sleep", LintLevel::Forbid, None),
+                ("unwrap", LintLevel::Deny, Some(Runtimes::Real)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_workspace_and_member() {
+        let workspace = PathBuf::from("Cargo.toml");
+        let member = PathBuf::from("robot/Cargo.toml");
+        let mut layers = Layers::default();
+        layers.push(
+            Origin::Project(workspace.clone()),
+            &serde_json::json!({
+                "team": 1234,
+                "robots": [],
+                "default-check": "real",
+                "lints": { "unwrap": "deny", "blocking-sleep": "warn" },
+                "git": {
+                    "require-clean": true,
+                    "competitions": [{ "acronym": "flr", "start": "2024-03-01", "end": "2024-03-03" }],
+                },
+            }),
+        );
+        layers.push(
+            Origin::Project(member.clone()),
+            &serde_json::json!({
+                "lints": { "blocking-sleep": "forbid" },
+                "git": {
+                    "competitions": [{ "acronym": "sfr", "start": "2024-03-08", "end": "2024-03-10" }],
+                },
+            }),
+        );
+        let (frc_cfg, origins) = layers.finish();
+        let dirs = TargetDirs::from_rel_target_dir(PathBuf::from("target"));
+        let config = parse_frc_table(&frc_cfg, "robot".to_owned(), dirs).unwrap();
+
+        let lints = config
+            .lints
+            .iter()
+            .map(|lint| (lint.name.as_str(), lint.level))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lints,
+            vec![
+                ("blocking-sleep", LintLevel::Forbid),
+                ("unwrap", LintLevel::Deny)
+            ]
+        );
+        assert!(config.git.require_clean);
+        let date = "2024-03-01".parse().unwrap();
+        assert!(config.git.active(date).is_none());
+        assert_eq!(
+            config.git.next(date).map(|c| c.acronym.as_str()),
+            Some("sfr")
+        );
+
+        let origin = |key: &str| match origins.get(key) {
+            Some(Origin::Project(path)) => Some(path.clone()),
+            _ => None,
+        };
+        assert_eq!(origin("lints.unwrap"), Some(workspace.clone()));
+        assert_eq!(origin("lints.blocking-sleep"), Some(member.clone()));
+        assert_eq!(origin("git.require-clean"), Some(workspace));
+        assert_eq!(origin("git.competitions"), Some(member.clone()));
+        assert_eq!(origin("lints"), Some(member));
+    }
+
+    #[test]
+    fn test_override_nested_key() {
+        let mut layers = Layers::default();
+        layers.push(
+            Origin::Project(PathBuf::from("frc.toml")),
+            &serde_json::json!({
+                "team": 1234,
+                "robots": [],
+                "default-check": "real",
+                "git": {
+                    "require-clean": true,
+                    "competitions": [{ "acronym": "flr", "start": "2024-03-01", "end": "2024-03-03" }],
+                },
+            }),
+        );
+        layers.push_cli(&ConfigOverrides {
+            require_clean: Some("false".to_owned()),
+            default_tool: Some("check".to_owned()),
+            ..Default::default()
+        });
+        let (frc_cfg, origins) = layers.finish();
+        let dirs = TargetDirs::from_rel_target_dir(PathBuf::from("target"));
+        let config = parse_frc_table(&frc_cfg, "robot".to_owned(), dirs).unwrap();
+        assert!(!config.git.require_clean);
+        assert_eq!(config.git.competitions.len(), 1);
+        assert_eq!(config.default_tool, CheckTool::Check);
+        assert!(matches!(
+            origins.get("git.require-clean"),
+            Some(Origin::Cli("--require-clean"))
+        ));
+        assert!(matches!(
+            origins.get("git.competitions"),
+            Some(Origin::Project(_))
+        ));
+    }
+}
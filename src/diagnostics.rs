@@ -0,0 +1,146 @@
+use std::{error::Error, io::Write, path::Path};
+
+use crate::{configs::project_config, errors::RuntimeError};
+
+const FRC_TABLES: [&str; 2] = ["[package.metadata.frc]", "[workspace.metadata.frc]"];
+
+/// Where in a manifest a diagnostic points to.
+#[derive(Debug, PartialEq, Eq)]
+struct Span {
+    line: usize,
+    column: usize,
+    len: usize,
+}
+
+/// Prints the error, its causes, a snippet of the offending config and a hint to stderr.
+pub fn report(err: &RuntimeError) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(stderr, "error: {}", err);
+
+    if let Some(attribute) = match err {
+        RuntimeError::Config(config_err) => config_err.attribute(),
+        _ => None,
+    } {
+        if let Some(frc_toml) = project_config::find_frc_toml(None) {
+            let _ = write_snippet(&mut stderr, &frc_toml, attribute, false);
+        } else if let Some(manifest) = project_config::find_manifest() {
+            let _ = write_snippet(&mut stderr, &manifest, attribute, true);
+        }
+    }
+
+    let mut source = err.source();
+    while let Some(cause) = source {
+        let _ = writeln!(stderr, "  caused by: {}", cause);
+        source = cause.source();
+    }
+
+    if let Some(help) = err.help() {
+        let _ = writeln!(stderr, "  help: {}", help);
+    }
+}
+
+/// `in_table` is whether the frc config is a table of the file, as in Cargo.toml,
+/// or the whole file, as in frc.toml.
+fn write_snippet(
+    out: &mut impl Write,
+    manifest: &Path,
+    attribute: &str,
+    in_table: bool,
+) -> std::io::Result<()> {
+    let contents = std::fs::read_to_string(manifest)?;
+    let span = if in_table {
+        locate_table(&contents).and_then(|table| {
+            locate_attribute(&contents, attribute, table.line + 1).or(Some(table))
+        })
+    } else {
+        locate_attribute(&contents, attribute, 0)
+    };
+    let Some(span) = span else {
+        return Ok(());
+    };
+    let line = contents.lines().nth(span.line).unwrap_or_default();
+    let number = (span.line + 1).to_string();
+    let gutter = " ".repeat(number.len());
+    writeln!(
+        out,
+        "{gutter}--> {}:{}:{}",
+        manifest.display(),
+        span.line + 1,
+        span.column + 1
+    )?;
+    writeln!(out, "{gutter} |")?;
+    writeln!(out, "{number} | {line}")?;
+    writeln!(
+        out,
+        "{gutter} | {}{}",
+        " ".repeat(span.column),
+        "^".repeat(span.len.max(1))
+    )?;
+    writeln!(out, "{gutter} |")
+}
+
+/// Finds the `[package.metadata.frc]` or `[workspace.metadata.frc]` header.
+fn locate_table(contents: &str) -> Option<Span> {
+    contents.lines().enumerate().find_map(|(line, text)| {
+        FRC_TABLES
+            .iter()
+            .find(|table| text.trim() == **table)
+            .map(|table| Span {
+                line,
+                column: 0,
+                len: table.len(),
+            })
+    })
+}
+
+/// Finds the value of a top level key of the frc config starting at line `start`,
+/// `attr` may be a path like `robots[i].name` in which case `robots` is located.
+fn locate_attribute(contents: &str, attr: &str, start: usize) -> Option<Span> {
+    let key = attr.split(['[', '.']).next()?;
+    contents
+        .lines()
+        .enumerate()
+        .skip(start)
+        .take_while(|(_, line)| !line.trim_start().starts_with('['))
+        .find_map(|(i, line)| {
+            let (lhs, rhs) = line.split_once('=')?;
+            if lhs.trim() != key {
+                return None;
+            }
+            let value = rhs.trim();
+            Some(Span {
+                line: i,
+                column: line.len() - rhs.trim_start().len(),
+                len: value.len(),
+            })
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MANIFEST: &str = "[package]\nname = \"robot\"\n\n[package.metadata.frc]\nteam = \"12a4\"\n\n[dependencies]\nteam = 1\n";
+
+    #[test]
+    fn test_locate_attribute() {
+        assert_eq!(
+            locate_attribute(MANIFEST, "team", 4),
+            Some(Span {
+                line: 4,
+                column: 7,
+                len: 6
+            })
+        );
+        assert_eq!(locate_attribute(MANIFEST, "robots[i].name", 4), None);
+        assert_eq!(
+            locate_attribute("team = 1234\nrobots = 4\n", "robots[i].name", 0),
+            Some(Span {
+                line: 1,
+                column: 9,
+                len: 1
+            })
+        );
+        assert_eq!(locate_table(MANIFEST).map(|span| span.line), Some(3));
+    }
+}
@@ -1,49 +1,484 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
-#[derive(Debug, Error, Clone, Copy)]
+use crate::actions::{
+    deploy_descriptor::{DescriptorParseError, EndpointError},
+    git_commit::GitError,
+    remote_probe::ProbeError,
+    ssh_client::SSHErrors,
+};
+
+/// The process exit code for each category of failure.
+///
+/// These are stable so scripts can tell failures apart,
+/// see the top of `main.rs` for the documented table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ExitCategory {
+    /// Anything that doesn't fit a more specific category.
+    Internal = 1,
+    /// The command line could not be parsed, reported by clap.
+    Usage = 2,
+    /// The project configuration is missing or invalid.
+    Config = 3,
+    /// The invoked cargo command failed, usually a compile error or failing test.
+    Cargo = 4,
+    /// The robot could not be reached over the network.
+    Unreachable = 5,
+    /// The robot was reached but deploying to it failed.
+    Deploy = 6,
+}
+
+#[derive(Debug, Error)]
 pub enum RuntimeError {
-    #[error("`frc check` failed: {0:?}")]
+    #[error("`frc check` failed")]
     Check(#[from] CheckSubcommandError),
-    #[error("`frc deploy` failed: {0:?}")]
+    #[error("`frc deploy` failed")]
     Deploy(#[from] DeploySubcommandError),
-    #[error("`frc sim` failed: {0:?}")]
+    #[error("`frc sim` failed")]
     Sim(#[from] SimSubcommandError),
-    #[error("`frc test` failed: {0:?}")]
+    #[error("`frc test` failed")]
     Test(#[from] TestSubcommandError),
-    #[error("`frc tui` failed: {0:?}")]
+    #[error("`frc tui` failed")]
     Tui(#[from] TuiSubcommandError),
-    #[error("`frc webservice` failed: {0:?}")]
+    #[error("`frc webservice` failed")]
     Webservice(#[from] WebserviceSubcommandError),
-    #[error("`frc set-team-number` failed: {0:?}")]
+    #[error("`frc status` failed")]
+    Status(#[from] StatusSubcommandError),
+    #[error("`frc logs` failed")]
+    Logs(#[from] LogsSubcommandError),
+    #[error("`frc fetch-logs` failed")]
+    FetchLogs(#[from] FetchLogsSubcommandError),
+    #[error("`frc set-team-number` failed")]
     SetTeamNumber(#[from] SetTeamNumberSubcommandError),
-    #[error("Failed to read config: {0:?}")]
+    #[error("`frc doctor` failed")]
+    Doctor(#[from] DoctorSubcommandError),
+    #[error("Failed to read config")]
     Config(#[from] ProjectConfigError),
+    #[error("Failed to create the log file {path:?}")]
+    LogFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 }
+impl RuntimeError {
+    pub fn category(&self) -> ExitCategory {
+        match self {
+            Self::Check(err) => err.category(),
+            Self::Test(err) => err.category(),
+            Self::Config(_) => ExitCategory::Config,
+            Self::LogFile { .. } => ExitCategory::Internal,
+            Self::Deploy(err) => err.category(),
+            Self::Status(err) => err.category(),
+            Self::Logs(err) => err.category(),
+            Self::FetchLogs(err) => err.category(),
+            Self::Doctor(err) => err.category(),
+            Self::Sim(err) => match *err {},
+            Self::Tui(err) => match *err {},
+            Self::Webservice(err) => match *err {},
+            Self::SetTeamNumber(err) => match *err {},
+        }
+    }
 
-#[derive(Debug, Error, Clone, Copy)]
+    /// A hint at how the user could fix the error, if there is one.
+    pub fn help(&self) -> Option<String> {
+        match self {
+            Self::Check(err) => err.help(),
+            Self::Test(err) => err.help(),
+            Self::Deploy(err) => err.help(),
+            Self::Status(err) => err.help(),
+            Self::Logs(err) => err.help(),
+            Self::FetchLogs(err) => err.help(),
+            Self::Doctor(err) => err.help(),
+            Self::Config(err) => err.help(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
 pub enum CheckSubcommandError {
     #[error("Failed to spawn `cargo check`")]
-    FailedToSpawnCargoCheck,
+    FailedToSpawnCargoCheck(#[source] std::io::Error),
     #[error("Failed to wait for `cargo check`")]
-    FailedToWaitForCargoCheck,
+    FailedToWaitForCargoCheck(#[source] std::io::Error),
     #[error("Failed to run `cargo check`: error {code:?}")]
     FailedCargoCheck { code: Option<i32> },
+    #[error("Invalid `--report`: {0}")]
+    InvalidReport(String),
+    #[error("Failed to write the report to {path:?}")]
+    WriteReport {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{failed} of {total} checked combinations failed")]
+    FailedMatrix { failed: usize, total: usize },
+    #[error("Failed to prepare the clippy config {path:?} for the `blocking-sleep` lint")]
+    ClippyConf {
+        path: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
+impl CheckSubcommandError {
+    fn category(&self) -> ExitCategory {
+        match self {
+            Self::FailedCargoCheck { .. } | Self::FailedMatrix { .. } => ExitCategory::Cargo,
+            Self::InvalidReport(_) => ExitCategory::Usage,
+            Self::ClippyConf { .. } => ExitCategory::Config,
+            _ => ExitCategory::Internal,
+        }
+    }
 
-#[derive(Debug, Error, Clone, Copy)]
-pub enum DeploySubcommandError {}
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::FailedToSpawnCargoCheck(_) => Some(CARGO_NOT_FOUND_HELP.to_owned()),
+            Self::ClippyConf { .. } => Some(
+                "fix the project's clippy.toml, or remove `blocking-sleep` from the lints table"
+                    .to_owned(),
+            ),
+            Self::FailedCargoCheck { .. } => Some("fix the errors reported by cargo above".to_owned()),
+            Self::FailedMatrix { .. } => {
+                Some("the errors of each failed combination are listed above the summary".to_owned())
+            }
+            Self::InvalidReport(_) => Some(REPORT_USAGE_HELP.to_owned()),
+            Self::FailedToWaitForCargoCheck(_) | Self::WriteReport { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DeploySubcommandError {
+    #[error("Failed to start the async runtime")]
+    Runtime(#[source] std::io::Error),
+    #[error("Failed to load the deploy descriptor")]
+    Descriptor(#[from] DescriptorParseError),
+    #[error("Failed to reach the robot")]
+    Unreachable(#[source] EndpointError),
+    #[error("Expected to deploy to {expected:?} but {found:?} is connected")]
+    WrongRobot { expected: String, found: String },
+    #[error("Refusing to deploy uncommitted changes, `require-clean` is set")]
+    DirtyTree,
+    #[error("The `deploy-files` directory {0:?} does not exist")]
+    MissingDeployFiles(PathBuf),
+    #[error("Failed to commit the deployed code")]
+    Git(#[from] GitError),
+    #[error("Failed to spawn `cargo build`")]
+    FailedToSpawnCargoBuild(#[source] std::io::Error),
+    #[error("Failed to wait for `cargo build`")]
+    FailedToWaitForCargoBuild(#[source] std::io::Error),
+    #[error("Failed to run `cargo build`: error {code:?}")]
+    FailedCargoBuild { code: Option<i32> },
+    #[error("`cargo build` did not produce an executable for {0:?}")]
+    MissingExecutable(String),
+    #[error("Failed to read {path:?}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to connect to the robot")]
+    Connect(#[source] SSHErrors),
+    #[error("Failed to talk to the robot")]
+    Remote(#[from] SSHErrors),
+    #[error("Failed to check the robot-code on the robot")]
+    Probe(#[from] ProbeError),
+    #[error("`{cmd}` exited with {code} on the robot")]
+    RemoteCommand { cmd: String, code: u32 },
+    #[error("The run-as user {0:?} does not exist on the robot")]
+    UnknownUser(String),
+    #[error("{path} is {found} on the robot instead of {expected}")]
+    Permissions {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    #[error("The robot failed the pre-deploy checks: {}", .0.join(", "))]
+    UnsafeTarget(Vec<String>),
+    #[error("{holder} has been deploying to the robot for {minutes} minutes")]
+    Locked {
+        holder: String,
+        minutes: i64,
+        /// After how many minutes the lock is stale.
+        expires: i64,
+    },
+    #[error("The deployed robot-code is unhealthy, {reason}{}", if *.rolled_back { ", the previous release was restored" } else { "" })]
+    Unhealthy { reason: String, rolled_back: bool },
+}
+impl DeploySubcommandError {
+    fn category(&self) -> ExitCategory {
+        match self {
+            Self::Unreachable(_) | Self::Connect(_) => ExitCategory::Unreachable,
+            Self::FailedCargoBuild { .. } => ExitCategory::Cargo,
+            Self::Descriptor(_) | Self::MissingDeployFiles(_) => ExitCategory::Config,
+            Self::Runtime(_)
+            | Self::Git(_)
+            | Self::FailedToSpawnCargoBuild(_)
+            | Self::FailedToWaitForCargoBuild(_)
+            | Self::MissingExecutable(_)
+            | Self::Read { .. } => ExitCategory::Internal,
+            Self::WrongRobot { .. }
+            | Self::DirtyTree
+            | Self::Remote(_)
+            | Self::Probe(_)
+            | Self::RemoteCommand { .. }
+            | Self::UnknownUser(_)
+            | Self::Permissions { .. }
+            | Self::UnsafeTarget(_)
+            | Self::Locked { .. }
+            | Self::Unhealthy { .. } => ExitCategory::Deploy,
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::Unreachable(_) | Self::Connect(_) => Some(ROBOT_NETWORK_HELP.to_owned()),
+            Self::WrongRobot { found, .. } => Some(format!(
+                "pass `--robot {found}` to deploy to the connected robot"
+            )),
+            Self::DirtyTree => Some(
+                "commit or stash your changes, or turn off git.require-clean".to_owned(),
+            ),
+            Self::MissingDeployFiles(_) => Some(
+                "create the directory, or fix `deploy-files` in the project config".to_owned(),
+            ),
+            Self::Git(GitError::Commit { .. }) => Some(
+                "set user.name and user.email in your git config, or pass --no-commit".to_owned(),
+            ),
+            Self::FailedToSpawnCargoBuild(_) => Some(CARGO_NOT_FOUND_HELP.to_owned()),
+            Self::FailedCargoBuild { .. } => Some("fix the errors reported by cargo above".to_owned()),
+            Self::MissingExecutable(_) => {
+                Some("make sure the robot package has a binary target".to_owned())
+            }
+            Self::UnknownUser(_) => Some(
+                "image the robot again, or fix `run_as` in the deploy descriptor".to_owned(),
+            ),
+            Self::Permissions { .. } => Some(
+                "the deploy user must be allowed to chown and chmod, check `root_user` in the deploy descriptor"
+                    .to_owned(),
+            ),
+            Self::UnsafeTarget(_) => {
+                Some("fix the robot, or pass `--force` to deploy anyway".to_owned())
+            }
+            Self::Locked { expires, .. } => Some(format!(
+                "wait for their deploy to finish, the lock expires after {expires} minutes, \
+                 or pass `--steal-lock` if it was abandoned"
+            )),
+            Self::Unhealthy { .. } => Some(
+                "the end of the robot-code's log is printed above, `cargo frc logs` shows all of it"
+                    .to_owned(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StatusSubcommandError {
+    #[error("Failed to start the async runtime")]
+    Runtime(#[source] std::io::Error),
+    #[error("Failed to load the deploy descriptor")]
+    Descriptor(#[from] DescriptorParseError),
+    #[error("None of the robots could be reached")]
+    Unreachable,
+}
+impl StatusSubcommandError {
+    fn category(&self) -> ExitCategory {
+        match self {
+            Self::Runtime(_) => ExitCategory::Internal,
+            Self::Descriptor(_) => ExitCategory::Config,
+            Self::Unreachable => ExitCategory::Unreachable,
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::Unreachable => Some(ROBOT_NETWORK_HELP.to_owned()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DoctorSubcommandError {
+    #[error("Failed to start the async runtime")]
+    Runtime(#[source] std::io::Error),
+    #[error("{failed} of {total} checks failed")]
+    Failed {
+        failed: usize,
+        total: usize,
+        /// The category of the first failed check.
+        category: ExitCategory,
+    },
+}
+impl DoctorSubcommandError {
+    fn category(&self) -> ExitCategory {
+        match self {
+            Self::Failed { category, .. } => *category,
+            Self::Runtime(_) => ExitCategory::Internal,
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::Failed { .. } => Some("each failed check above says how to fix it".to_owned()),
+            Self::Runtime(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LogsSubcommandError {
+    #[error("Failed to start the async runtime")]
+    Runtime(#[source] std::io::Error),
+    #[error("Failed to load the deploy descriptor")]
+    Descriptor(#[from] DescriptorParseError),
+    #[error("Failed to connect to the robot")]
+    Connect(#[source] SSHErrors),
+    #[error("Failed to talk to the robot")]
+    Remote(#[from] SSHErrors),
+    #[error("`{cmd}` exited with {code} on the robot")]
+    RemoteCommand { cmd: String, code: u32 },
+    #[error("Failed to save the log to {path:?}")]
+    Save {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+impl LogsSubcommandError {
+    fn category(&self) -> ExitCategory {
+        match self {
+            Self::Connect(_) => ExitCategory::Unreachable,
+            Self::Descriptor(_) => ExitCategory::Config,
+            Self::Runtime(_) | Self::Remote(_) | Self::RemoteCommand { .. } | Self::Save { .. } => {
+                ExitCategory::Internal
+            }
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::Connect(_) => Some(ROBOT_NETWORK_HELP.to_owned()),
+            Self::RemoteCommand { .. } => {
+                Some("make sure robot-code has been deployed and started at least once".to_owned())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FetchLogsSubcommandError {
+    #[error("Failed to start the async runtime")]
+    Runtime(#[source] std::io::Error),
+    #[error("Failed to load the deploy descriptor")]
+    Descriptor(#[from] DescriptorParseError),
+    #[error("Failed to reach the robot")]
+    Unreachable(#[source] EndpointError),
+    #[error("Failed to connect to the robot")]
+    Connect(#[source] SSHErrors),
+    #[error("Failed to talk to the robot")]
+    Remote(#[from] SSHErrors),
+    #[error("Failed to write {path:?}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+impl FetchLogsSubcommandError {
+    fn category(&self) -> ExitCategory {
+        match self {
+            Self::Unreachable(_) | Self::Connect(_) => ExitCategory::Unreachable,
+            Self::Descriptor(_) => ExitCategory::Config,
+            Self::Runtime(_) | Self::Remote(_) | Self::Io { .. } => ExitCategory::Internal,
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::Unreachable(_) | Self::Connect(_) => Some(ROBOT_NETWORK_HELP.to_owned()),
+            Self::Remote(_) => Some(
+                "run `cargo frc fetch-logs` again, finished logs are kept and partial ones resumed"
+                    .to_owned(),
+            ),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Error, Clone, Copy)]
 pub enum SimSubcommandError {}
 
-#[derive(Debug, Error, Clone, Copy)]
+#[derive(Debug, Error)]
 pub enum TestSubcommandError {
     #[error("Failed to spawn `cargo test`")]
-    FailedToSpawnCargoTest,
+    FailedToSpawnCargoTest(#[source] std::io::Error),
     #[error("Failed to wait for `cargo test`")]
-    FailedToWaitForCargoTest,
+    FailedToWaitForCargoTest(#[source] std::io::Error),
     #[error("Failed to run `cargo test`: error {code:?}")]
     FailedCargoTest { code: Option<i32> },
+    #[error("Failed to start the async runtime")]
+    Runtime(#[source] std::io::Error),
+    #[error("Failed to load the deploy descriptor")]
+    Descriptor(#[from] DescriptorParseError),
+    #[error("Failed to connect to the robot")]
+    Connect(#[source] SSHErrors),
+    #[error("Failed to run the tests on the robot")]
+    Remote(#[from] SSHErrors),
+    #[error("Failed to read {path:?}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to run the test binary {path:?}")]
+    SpawnTestBinary {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to wait for the test binary {path:?}")]
+    WaitTestBinary {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Tests failed in {}", .0.join(", "))]
+    FailedTests(Vec<String>),
+    #[error("Invalid `--report`: {0}")]
+    InvalidReport(String),
+    #[error("Failed to write the report to {path:?}")]
+    WriteReport {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+impl TestSubcommandError {
+    fn category(&self) -> ExitCategory {
+        match self {
+            Self::FailedCargoTest { .. } | Self::FailedTests(_) => ExitCategory::Cargo,
+            Self::InvalidReport(_) => ExitCategory::Usage,
+            Self::Connect(_) => ExitCategory::Unreachable,
+            Self::Descriptor(_) => ExitCategory::Config,
+            _ => ExitCategory::Internal,
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::FailedToSpawnCargoTest(_) => Some(CARGO_NOT_FOUND_HELP.to_owned()),
+            Self::FailedCargoTest { .. } => {
+                Some("fix the failing tests or compile errors reported by cargo above".to_owned())
+            }
+            Self::FailedTests(_) => Some("fix the failing tests reported above".to_owned()),
+            Self::InvalidReport(_) => Some(REPORT_USAGE_HELP.to_owned()),
+            Self::Connect(_) => Some(ROBOT_NETWORK_HELP.to_owned()),
+            Self::FailedToWaitForCargoTest(_)
+            | Self::Descriptor(_)
+            | Self::Runtime(_)
+            | Self::Remote(_)
+            | Self::Read { .. }
+            | Self::SpawnTestBinary { .. }
+            | Self::WaitTestBinary { .. }
+            | Self::WriteReport { .. } => None,
+        }
+    }
 }
 
 #[derive(Debug, Error, Clone, Copy)]
@@ -55,21 +490,73 @@ pub enum WebserviceSubcommandError {}
 #[derive(Debug, Error, Clone, Copy)]
 pub enum SetTeamNumberSubcommandError {}
 
-#[derive(Debug, Error, Clone, Copy)]
+const REPORT_USAGE_HELP: &str = "use `--report junit <PATH>` or `--report json <PATH>`";
+const CARGO_NOT_FOUND_HELP: &str = "make sure cargo is installed and on your PATH, or point $CARGO at it";
+const ROBOT_NETWORK_HELP: &str =
+    "make sure this computer is connected to the robot's network, or set --address";
+
+#[derive(Debug, Error)]
 pub enum ProjectConfigError {
     #[error("Error reading cargo metadata")]
-    CargoMetadata,
-    #[error("Failed to get root package")]
-    MissingRootPackage,
-    #[error("[package.metadata.frc] not found in cargo metadata")]
+    CargoMetadata(#[from] cargo_metadata::Error),
+    #[error("Failed to read {path:?}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to parse {path:?}")]
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Package {0:?} is not a member of the workspace")]
+    UnknownPackage(String),
+    #[error("Could not tell which of the workspace members {0:?} is the robot package")]
+    AmbiguousPackage(Vec<String>),
+    #[error("Neither [package.metadata.frc] nor [workspace.metadata.frc] found in cargo metadata")]
     MissingFrcMetadata,
-    #[error("Failed to get attribute {0:?} from [package.metadata.frc]")]
+    #[error("Failed to get attribute {0:?} from the frc config")]
     MissingAttribute(&'static str),
-    #[error("Failed to parse attribute {0:?} from [package.metadata.frc], should be {1:?}")]
+    #[error("Failed to parse attribute {0:?} from the frc config, should be {1}")]
     ParseAttribute(&'static str, &'static str),
+    #[error("Competition {0:?} ends before it starts")]
+    InvertedCompetition(String),
+    #[error("Competitions {0:?} and {1:?} overlap")]
+    OverlappingCompetitions(String, String),
+    #[error("Invalid value from {0}")]
+    InvalidOverride(String, #[source] Box<ProjectConfigError>),
 }
-impl From<cargo_metadata::Error> for ProjectConfigError {
-    fn from(_: cargo_metadata::Error) -> Self {
-        Self::CargoMetadata
+impl ProjectConfigError {
+    /// The frc config attribute the error is about, if any.
+    pub fn attribute(&self) -> Option<&'static str> {
+        match self {
+            Self::MissingAttribute(attr) | Self::ParseAttribute(attr, _) => Some(attr),
+            _ => None,
+        }
+    }
+
+    fn help(&self) -> Option<String> {
+        match self {
+            Self::CargoMetadata(_) => Some(
+                "run `cargo metadata` in this directory to see why cargo can't read the project"
+                    .to_owned(),
+            ),
+            Self::Io { .. } | Self::Toml { .. } => None,
+            Self::UnknownPackage(_) | Self::AmbiguousPackage(_) => Some(
+                "select the robot package with `-p <name>` or run from its directory".to_owned(),
+            ),
+            Self::MissingFrcMetadata => Some(
+                "add a [package.metadata.frc] table with at least `team` and `default-check` to Cargo.toml"
+                    .to_owned(),
+            ),
+            Self::MissingAttribute(attr) => {
+                Some(format!("add `{attr} = ...` to [package.metadata.frc] or frc.toml"))
+            }
+            Self::InvertedCompetition(_) | Self::OverlappingCompetitions(..) => Some(
+                "fix the start and end dates in [package.metadata.frc.git] competitions".to_owned(),
+            ),
+            // the message and the snippet already say what is wrong and what is expected
+            Self::ParseAttribute(..) | Self::InvalidOverride(..) => None,
+        }
     }
 }
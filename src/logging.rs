@@ -0,0 +1,105 @@
+use std::{fs::File, path::PathBuf, sync::Arc};
+
+use clap::{ArgAction, Args, ValueEnum};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{
+    fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, Layer, Registry,
+};
+
+use crate::errors::RuntimeError;
+
+/// How log lines are formatted, on stderr and in the `--log-file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+/// How much cargo-frc logs and where to, for every subcommand.
+///
+/// `-v` and `-q` are also passed on to cargo by `check` and `test`.
+#[derive(Debug, Args)]
+pub struct LogArgs {
+    /// Log more, `-vv` for everything, also shows how long each phase took
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "pretty")]
+    pub log_format: LogFormat,
+
+    /// Also write the log to PATH, always including debug lines and phase timings
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+}
+impl LogArgs {
+    fn level(&self) -> LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => LevelFilter::ERROR,
+            (false, 0) => LevelFilter::INFO,
+            (false, 1) => LevelFilter::DEBUG,
+            (false, _) => LevelFilter::TRACE,
+        }
+    }
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Installs the subscriber for the `tracing` macros used throughout cargo-frc.
+///
+/// Logs go to stderr so stdout stays free for the output of the subcommands.
+/// The phases of a command (config, build, connect, upload, restart) are spans,
+/// their timing is logged when they close.
+pub fn init(args: &LogArgs) -> Result<(), RuntimeError> {
+    let level = args.level();
+    let stderr_spans = if args.verbose > 0 {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+    let stderr = match args.log_format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_target(args.verbose > 1)
+            .without_time()
+            .with_span_events(stderr_spans)
+            .with_filter(level)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(std::io::stderr)
+            .with_span_events(stderr_spans)
+            .with_filter(level)
+            .boxed(),
+    };
+    let mut layers: Vec<BoxedLayer> = vec![stderr];
+
+    if let Some(path) = &args.log_file {
+        let file = File::create(path).map_err(|source| RuntimeError::LogFile {
+            path: path.clone(),
+            source,
+        })?;
+        let file = Arc::new(file);
+        let level = level.max(LevelFilter::DEBUG);
+        layers.push(match args.log_format {
+            LogFormat::Pretty => tracing_subscriber::fmt::layer()
+                .with_writer(file)
+                .with_ansi(false)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_filter(level)
+                .boxed(),
+            LogFormat::Json => tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(file)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_filter(level)
+                .boxed(),
+        });
+    }
+
+    tracing_subscriber::registry().with(layers).init();
+    Ok(())
+}
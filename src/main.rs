@@ -1,11 +1,16 @@
 mod actions;
 mod cmds;
 mod configs;
+mod diagnostics;
 mod errors;
+mod logging;
+mod output;
 
-use clap::Parser;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
 use errors::RuntimeError;
-use configs::project_config;
+use configs::{layers::ConfigOverrides, project_config};
 
 // # cargo-frc
 // cargo-frc is a Cargo subcommand for building and deploying Rust code in the FRC ecosystem.
@@ -14,35 +19,177 @@ use configs::project_config;
 // ## Commands
 //
 // ### `cargo frc check`
-//    Runs `cargo check` with the correct flags for the FRC ecosystem.]
+//    Runs `cargo clippy`, or `cargo check` with `--tool check` or `default-tool = "check"`,
+//    with the correct flags for the FRC ecosystem.
+//    The `[package.metadata.frc.lints]` table sets lint levels, either for every runtime
+//    (`unwrap = "deny"`) or for one (`unwrap = { level = "deny", runtime = "real" }`).
+//    Besides rustc and clippy lints it knows the FRC lints `unwrap`, `expect`, `panic`
+//    and `blocking-sleep`, which disallows `std::thread::sleep`.
+//    `--matrix` checks real and sim, each with and without dev, in parallel
+//    and for every `--matrix-features` set, then prints which combinations failed.
+//
+// `check` and `test` take `--report junit|json <PATH>` to write the compiler diagnostics,
+// the test results and the cfgs they were built with to a file.
 //
 // ### `cargo frc sim`
 //    Runs `cargo run` to run code on local machine with the `frc_sim` cfg.
 //
 // ### `cargo frc deploy`
 //    Deploys code to the robot with the `frc_real`.
+//    During a competition configured in `[package.metadata.frc.git]` the deployed code
+//    is also committed to the `comp/<acronym>` branch once it started and passed its health check,
+//    untracked files that aren't ignored included.
+//    A `frc-deploy.json` manifest recording the git provenance is written next to the binary,
+//    and `git.require-clean = true` refuses deploys of uncommitted changes outside competitions.
+//    Deployed files are handed to the descriptor's `run_as` user, the binary with mode `0755`,
+//    and the deploy fails if that didn't stick.
+//    The descriptor's `robot_command` file (`/home/lvuser/robotCommand` on a roboRIO) is
+//    pointed at the deployed binary, it's what `start_cmd` runs.
+//    The contents of the local `deploy-files` directory are copied into `deploy-dir` on the robot,
+//    relative to the deploy user's home (the descriptor's `extra_files_path` if unset).
+//    Files already on the robot that aren't part of `deploy-files` are left alone.
+//    Before anything is stopped, the robot must pass the descriptor's `pre_deploy_checks`
+//    (free space, an FRC OS image, clock skew), `--force` deploys anyway.
+//    Descriptors can also probe that the robot is not enabled, the builtin rio descriptor
+//    doesn't since the roboRIO has no documented way to read it, so every deploy without
+//    such a probe warns to disable the robot first.
+//    After the start the deploy waits for the descriptor's `health_check`, a running process,
+//    a log line, or an HTTP or TCP readiness probe, and prints the end of the log if it fails.
+//    With `rollback` the previous binary, manifest, `robot_command` and deploy dir are kept
+//    as `.prev` and restored then, unless the deploy dir is the deploy user's home or above it.
+//    While deploying, a `frc-deploy.lock` naming the deploying host, user and commit keeps
+//    other deploys out, `--steal-lock` takes it anyway, and it expires after 10 minutes.
+//
+// Builds get `FRC_GIT_SHA`, `FRC_GIT_BRANCH`, `FRC_GIT_DIRTY` and `FRC_GIT_DIFF_HASH`
+// set in their environment for use with `env!`. `check` only sets the commit and branch,
+// its builds never run, so they always count as clean.
 //
 // ### `cargo frc tui`
 //    Runs the TUI for managing the robot.
 //
 // ### `cargo frc test`
 //    Runs `cargo test` with the correct flags for the FRC ecosystem.
+//    With `--real` the tests are built for the robot, uploaded next to the robot-code
+//    and run there one binary at a time while the robot program is stopped.
+//    Like `cargo test [TESTNAME] [-- ARGS]`, TESTNAME filters the tests and the args
+//    after `--` go to the test binaries.
+//    With `--real`, `--report` or `--json` cargo-frc runs the test binaries itself
+//    and doc tests are not run, `cargo test` only runs them for the host.
 //
 // ### `cargo frc set-team-number`
 //    Sets the team number of the connected robot to the one of the current project.
 //
 // ### `cargo frc webservice`
 //    Utilities for interacting with the robot's web service and a custom web service.
+//
+// ### `cargo frc comp status`
+//    Shows the active and next competition from `[package.metadata.frc.git]`.
+//
+// ### `cargo frc status`
+//    Shows what is deployed on every reachable robot from its `frc-deploy.json`,
+//    and how it compares to the local HEAD and work tree. Exits with 5 if no robot could be reached.
+//
+// ### `cargo frc logs [-f] [--since <WHEN>] [--level <LEVEL>] [--grep <REGEX>] [--save]`
+//    Prints the console log of the robot-code, the `log_path` of the deploy descriptor.
+//    `--save` also writes the shown lines to a timestamped file under `<target-dir>/real/logs`.
+//
+// ### `cargo frc fetch-logs [--delete] [--dir <DIR>]`
+//    Downloads the datalogs matching the descriptor's `datalog_globs` into `./logs/<robot>/<event>/`,
+//    skipping logs already archived and resuming interrupted downloads.
+//    `--delete` removes logs from the robot once their sha256 matches the archived copy.
+//
+// ### `cargo frc doctor`
+//    Checks the project config, the deploy descriptors, the rust target and cross linker,
+//    and that every robot is reachable, accepts SSH and has free space under the deploy path.
+//    Prints a checklist with a hint for everything that warned or failed.
+//    The linker check is best-effort, it reads `[target.<triple>] linker` from the environment
+//    and the `.cargo/config.toml` files from the manifest's directory up and in `CARGO_HOME`.
+//    Doctor exits with the category of the first failed check, e.g. 3 for a missing target
+//    or 5 for an unreachable robot.
+//
+// ### `cargo frc config show [--origin]`
+//    Prints the effective project config, optionally with where each value came from.
+//
+// ## Logging
+//
+// Every command logs to stderr, `-v`/`-vv` for more and `-q` for only errors,
+// `--log-format json` for one json object per line, `--log-file <PATH>` to also keep a log
+// with debug lines and the time each phase (config, build, connect, upload, restart) took.
+//
+// ## JSON Output
+//
+// With `--json` a command prints one json document on stdout when it's done,
+// while progress, logs, compiler and test output go to stderr. Failures still
+// set the exit code below, and the error is printed to stderr.
+//
+// - `check`, `test`: the report of `--report json`, `{command, runtime, dev, cfgs, success,
+//   diagnostics: [{level, message, code, file, line, column, rendered}],
+//   suites: [{name, time, tests: [{name, outcome, time, output}]}]}`.
+// - `check --matrix`: `{success, combinations: [...]}`, each a check report with its `features`.
+// - `deploy`: `{robot, serial, address, binary, files, bytes, duration}`, `duration` in seconds.
+// - `status`: `{local, robots: [{robot, address, reachable, serial, error, deployed,
+//   compared_to_local}]}`, `local` and `deployed.git` as in `frc-deploy.json`.
+// - `logs`: one `{line, level}` per line instead of a single document.
+// - `fetch-logs`: `{robot, event, dir, fetched, skipped, deleted}`.
+// - `doctor`: `{success, checks: [{name, status, detail, hint}]}`, `status` is `pass`, `warn` or `fail`.
+// - `config show`: the keys of `config show` with typed values, plus `address` and `origins`.
+// - `comp status`: `{active: {acronym, start, end, day, days, branch}, next: {acronym, start,
+//   end, starts_in_days}}`, either `null` when there is none.
+//
+// `sim`, `tui`, `webservice` and `set-team-number` are not implemented yet and have no json
+// output, so querying the robot's web service as json is out of scope until `webservice` is.
+//
+// Fields are only ever added to these, a missing value is `null`.
+//
+// ## Configuration
+//
+// The project is configured in `[package.metadata.frc]` (or `[workspace.metadata.frc]`),
+// or in an `frc.toml` next to Cargo.toml with the same keys, which is read without invoking cargo.
+// Values are resolved with the precedence: command line flags (`--team`, `--address`, `--robot`,
+// `--deploy-dir`, `--deploy-files`, `--default-check`, `--default-tool`, `--require-clean`),
+// then `FRC_*` environment variables (`FRC_TEAM_NUMBER`, `FRC_ADDRESS`, `FRC_ROBOT`,
+// `FRC_DEPLOY_DIR`, `FRC_DEPLOY_FILES`, `FRC_DEFAULT_CHECK`, `FRC_DEFAULT_TOOL`,
+// `FRC_REQUIRE_CLEAN`), then the project config,
+// then the global config at `~/.config/cargo-frc/config.toml` (or `$FRC_GLOBAL_CONFIG`).
+// A `deploy-files` from a config file is relative to that file, from an override to the current directory.
+// Tables like `git` and `lints` are merged key by key, other values (arrays included) are replaced whole.
+// A robot is reached at `override-address` (`--address`), else the `address` of the robot,
+// else the team's `10.TE.AM.2`.
+//
+// ## Exit Codes
+//
+// | Code | Meaning                                                    |
+// |------|------------------------------------------------------------|
+// | 0    | Success                                                    |
+// | 1    | Internal error, anything not covered below                 |
+// | 2    | The command line could not be parsed                       |
+// | 3    | The project configuration is missing or invalid            |
+// | 4    | The underlying cargo command failed (compile error, tests) |
+// | 5    | The robot could not be reached                             |
+// | 6    | The robot was reached but deploying to it failed           |
 
 #[derive(Parser)]
 #[command(bin_name = "frc", version, author, disable_help_subcommand = true)]
+struct Cli {
+    #[command(flatten)]
+    overrides: ConfigOverrides,
+    #[command(flatten)]
+    log: logging::LogArgs,
+    /// Print the result as json on stdout, everything else goes to stderr
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
 enum Commands {
     #[clap(name = "check")]
     Check(cmds::checker::Check),
     #[clap(name = "sim")]
     Sim,
     #[clap(name = "deploy")]
-    Deploy,
+    Deploy(cmds::deployer::Deploy),
     #[clap(name = "test")]
     Test(cmds::tester::Test),
     #[clap(name = "tui")]
@@ -51,23 +198,113 @@ enum Commands {
     Webservice,
     #[clap(name = "set-team-number")]
     SetTeamNumber,
+    #[clap(name = "status")]
+    Status(cmds::status::Status),
+    #[clap(name = "logs")]
+    Logs(cmds::logs::Logs),
+    #[clap(name = "fetch-logs")]
+    FetchLogs(cmds::fetch_logs::FetchLogs),
+    #[clap(name = "doctor")]
+    Doctor(cmds::doctor::Doctor),
+    #[clap(name = "config", subcommand)]
+    Config(cmds::configurer::Config),
+    #[clap(name = "comp", subcommand)]
+    Comp(cmds::competition::Comp),
 }
 
 cargo_subcommand_metadata::description!(
     "Manage building and deploying Rust code in the FRC ecosystem."
 );
 
-fn main() -> Result<(), RuntimeError> {
-    let config = project_config::read_config2()?;
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            diagnostics::report(&err);
+            ExitCode::from(err.category() as u8)
+        }
+    }
+}
+
+fn run() -> Result<(), RuntimeError> {
     // remove frc from args
     let mut args = std::env::args().collect::<Vec<String>>();
     if args.len() > 1 && args[1] == "frc" {
         args.remove(1);
     }
-    let commands = Commands::parse_from(args);
-    match commands {
-        Commands::Check(check) => cmds::checker::cargo_check(check, config)?,
-        Commands::Test(test) => cmds::tester::cargo_test(test, config)?,
+    let cli = Cli::parse_from(args);
+    logging::init(&cli.log)?;
+    match cli.command {
+        Commands::Check(mut check) => {
+            check.verbose = cli.log.verbose > 0;
+            check.quiet = cli.log.quiet;
+            check.json = cli.json;
+            let config = project_config::read_config2(
+                &cli.overrides,
+                check.manifest_path.as_deref(),
+                check.package.clone().flatten().as_deref(),
+            )?;
+            cmds::checker::cargo_check(check, config)?
+        }
+        Commands::Test(mut test) => {
+            test.verbose = cli.log.verbose > 0;
+            test.quiet = cli.log.quiet;
+            test.json = cli.json;
+            let config = project_config::read_config2(
+                &cli.overrides,
+                test.manifest_path.as_deref(),
+                test.package.clone().flatten().as_deref(),
+            )?;
+            cmds::tester::cargo_test(test, config)?
+        }
+        Commands::Deploy(mut deploy) => {
+            deploy.json = cli.json;
+            let config = project_config::read_config2(
+                &cli.overrides,
+                deploy.manifest_path.as_deref(),
+                deploy.package.as_deref(),
+            )?;
+            cmds::deployer::cargo_deploy(deploy, config)?
+        }
+        Commands::Status(mut status) => {
+            status.json = cli.json;
+            let config = project_config::read_config2(
+                &cli.overrides,
+                status.manifest_path.as_deref(),
+                status.package.as_deref(),
+            )?;
+            cmds::status::cargo_status(status, config)?
+        }
+        Commands::Logs(mut logs) => {
+            logs.json = cli.json;
+            let config = project_config::read_config2(
+                &cli.overrides,
+                logs.manifest_path.as_deref(),
+                logs.package.as_deref(),
+            )?;
+            cmds::logs::cargo_logs(logs, config)?
+        }
+        Commands::FetchLogs(mut fetch) => {
+            fetch.json = cli.json;
+            let config = project_config::read_config2(
+                &cli.overrides,
+                fetch.manifest_path.as_deref(),
+                fetch.package.as_deref(),
+            )?;
+            cmds::fetch_logs::cargo_fetch_logs(fetch, config)?
+        }
+        Commands::Doctor(mut doctor) => {
+            doctor.json = cli.json;
+            cmds::doctor::cargo_doctor(doctor, &cli.overrides)?
+        }
+        Commands::Config(command) => {
+            let config = project_config::read_config2(&cli.overrides, None, None)?;
+            cmds::configurer::cargo_config(command, config, cli.json)
+        }
+        Commands::Comp(command) => {
+            let config = project_config::read_config2(&cli.overrides, None, None)?;
+            cmds::competition::cargo_comp(command, config, cli.json)
+        }
         _ => {
             unimplemented!();
         }
@@ -0,0 +1,11 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+/// Prints the `--json` output of a subcommand as one line on stdout.
+///
+/// Everything meant for humans goes to stderr in this mode, so stdout is only json.
+pub fn print_json(value: &impl Serialize) {
+    let json = serde_json::to_string(value).expect("the output is serializable");
+    let _ = writeln!(std::io::stdout().lock(), "{}", json);
+}